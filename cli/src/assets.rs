@@ -0,0 +1,243 @@
+use anyhow::{Context, Result};
+use comrak::nodes::{AstNode, NodeValue};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::config::ImagesSection;
+use crate::images::{self, Picture};
+use crate::links::{decode_path, is_local, join_relative};
+use crate::markdown_meta::is_markdown;
+
+/// Referenced files are copied to `assets/<name>.<hash>.<ext>` in the output dir.
+pub const ASSET_DIR: &str = "assets";
+
+/// Stands in for an asset's URL until it has been copied.
+const PLACEHOLDER_SCHEME: &str = "sitegen-asset:";
+
+/// An image or file a post points at with a relative path.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PostAsset {
+    /// As written, e.g. `./img/diagram.png`
+    pub written: String,
+    /// The file, relative to the working directory like the post's own path
+    pub source: PathBuf,
+    /// `?query` or `#fragment` kept on the rewritten URL
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub suffix: String,
+    pub line: usize,
+    /// What `content` holds for this asset right now
+    pub href: String,
+    /// Written as `![..](..)` rather than a link, so it can become a `<picture>`
+    #[serde(default)]
+    pub image: bool,
+}
+
+/// Swap every local image, and every link to a local file that is not a post, for a placeholder.
+pub fn collect_post_assets<'a>(root: &'a AstNode<'a>, path: &Path) -> Vec<PostAsset> {
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let mut assets = Vec::new();
+    for node in root.descendants() {
+        let mut ast = node.data.borrow_mut();
+        let line = ast.sourcepos.start.line;
+        let image = matches!(ast.value, NodeValue::Image(_));
+        let (NodeValue::Image(ref mut link) | NodeValue::Link(ref mut link)) = ast.value else {
+            continue;
+        };
+        if !is_local(&link.url) {
+            continue;
+        }
+        let split = link.url.find(['?', '#']).unwrap_or(link.url.len());
+        let (file, suffix) = link.url.split_at(split);
+        let source = join_relative(dir, &decode_path(file));
+        if is_markdown(&source) {
+            continue;
+        }
+        let href = format!("{PLACEHOLDER_SCHEME}{}", assets.len());
+        assets.push(PostAsset {
+            written: link.url.clone(),
+            source,
+            suffix: suffix.to_owned(),
+            line,
+            href: href.clone(),
+            image,
+        });
+        link.url = href;
+    }
+    assets
+}
+
+/// Where a referenced file ended up.
+#[derive(Debug, Clone)]
+pub struct Published {
+    /// Site relative URL of the copy
+    pub url: String,
+    /// Resized variants, for images `images` is enabled for
+    pub picture: Option<Picture>,
+}
+
+/// Copies each referenced file into the output dir once, under a name that changes with its content.
+pub struct AssetStore<'a> {
+    dist_dir: &'a Path,
+    images: &'a ImagesSection,
+    /// canonical source -> where it was copied to
+    published: HashMap<PathBuf, Published>,
+    /// canonical source -> blurred placeholder, kept from decoding an image for its variants
+    placeholders: HashMap<PathBuf, Option<String>>,
+    /// Used by posts left out of this build, not copied but not unreferenced either
+    held_back: HashSet<PathBuf>,
+    written: BTreeSet<PathBuf>,
+}
+
+impl<'a> AssetStore<'a> {
+    pub fn new(dist_dir: &'a Path, images: &'a ImagesSection) -> Self {
+        Self {
+            dist_dir,
+            images,
+            published: HashMap::new(),
+            placeholders: HashMap::new(),
+            held_back: HashSet::new(),
+            written: BTreeSet::new(),
+        }
+    }
+
+    /// Where the copy went, `None` when `source` does not exist.
+    pub fn publish(&mut self, source: &Path) -> Result<Option<Published>> {
+        let Ok(canonical) = fs::canonicalize(source) else {
+            return Ok(None);
+        };
+        if !canonical.is_file() {
+            return Ok(None);
+        }
+        if let Some(published) = self.published.get(&canonical) {
+            return Ok(Some(published.clone()));
+        }
+        let bytes = fs::read(&canonical)
+            .with_context(|| format!("failed to read: {}", source.display()))?;
+        let hash = hex::encode(Sha256::digest(&bytes));
+        let stem = source
+            .file_stem()
+            .map(|s| s.to_string_lossy())
+            .unwrap_or_default();
+        let name = match source.extension() {
+            Some(ext) => format!("{stem}.{}.{}", &hash[..10], ext.to_string_lossy()),
+            None => format!("{stem}.{}", &hash[..10]),
+        };
+        let url = format!("{ASSET_DIR}/{name}");
+        let dest = self.dist_dir.join(ASSET_DIR).join(&name);
+        // 文件名带内容哈希, 已经存在就是同一个文件
+        if !dest.is_file() {
+            fs::create_dir_all(self.dist_dir.join(ASSET_DIR))
+                .with_context(|| format!("failed to create {ASSET_DIR} in the output dir"))?;
+            fs::write(&dest, &bytes)
+                .with_context(|| format!("write to {} failed", dest.display()))?;
+        }
+        self.written.insert(dest);
+        let picture = if self.images.enabled && images::is_resizable(source) {
+            match self.publish_variants(source, &canonical, &stem, &hash) {
+                Ok(picture) => Some(picture),
+                Err(e) => {
+                    eprintln!(
+                        "warning: {}: {e:#}, copied without resized variants",
+                        source.display()
+                    );
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        let published = Published { url, picture };
+        self.published.insert(canonical, published.clone());
+        Ok(Some(published))
+    }
+
+    /// Work out an image's variants and write the ones not there from an earlier build.
+    fn publish_variants(
+        &mut self,
+        source: &Path,
+        canonical: &Path,
+        stem: &str,
+        hash: &str,
+    ) -> Result<Picture> {
+        let picture = images::plan(source, stem, hash, self.images)?;
+        let dir = self.dist_dir.join(ASSET_DIR);
+        self.written.extend(
+            picture
+                .variants
+                .iter()
+                .map(|variant| dir.join(&variant.name)),
+        );
+        if picture
+            .variants
+            .iter()
+            .any(|variant| !dir.join(&variant.name).is_file())
+        {
+            let placeholder = images::generate(source, &picture, &dir, self.images)?;
+            self.placeholders
+                .insert(canonical.to_path_buf(), placeholder);
+        }
+        Ok(picture)
+    }
+
+    /// Blurred placeholder of a published image, decoded again only if `publish` did not have to.
+    pub fn placeholder(&mut self, source: &Path) -> Result<Option<String>> {
+        let canonical = fs::canonicalize(source)
+            .with_context(|| format!("failed to read: {}", source.display()))?;
+        if let Some(placeholder) = self.placeholders.get(&canonical) {
+            return Ok(placeholder.clone());
+        }
+        let placeholder = images::placeholder(&images::decode(source)?);
+        self.placeholders.insert(canonical, placeholder.clone());
+        Ok(placeholder)
+    }
+
+    pub fn hold_back(&mut self, source: &Path) {
+        if let Ok(canonical) = fs::canonicalize(source) {
+            self.held_back.insert(canonical);
+        }
+    }
+
+    /// Every file copied for this build.
+    pub fn written(&self) -> BTreeSet<PathBuf> {
+        self.written.clone()
+    }
+
+    /// Files under `roots` that are neither posts nor referenced by one.
+    pub fn unreferenced(&self, roots: &[PathBuf]) -> Vec<PathBuf> {
+        let mut found = Vec::new();
+        let dist_dir = fs::canonicalize(self.dist_dir).ok();
+        for root in roots {
+            let walker = walkdir::WalkDir::new(root)
+                .follow_links(true)
+                .into_iter()
+                // 隐藏文件和目录不算, 输出目录在内容目录里面时也跳过
+                .filter_entry(|entry| {
+                    let hidden =
+                        entry.depth() > 0 && entry.file_name().to_string_lossy().starts_with('.');
+                    let output = entry.file_type().is_dir()
+                        && dist_dir.is_some()
+                        && fs::canonicalize(entry.path()).ok() == dist_dir;
+                    !hidden && !output
+                });
+            for entry in walker.flatten() {
+                if !entry.file_type().is_file() || is_markdown(entry.path()) {
+                    continue;
+                }
+                let referenced = fs::canonicalize(entry.path()).is_ok_and(|canonical| {
+                    self.published.contains_key(&canonical) || self.held_back.contains(&canonical)
+                });
+                if !referenced {
+                    found.push(entry.into_path());
+                }
+            }
+        }
+        found.sort();
+        found.dedup();
+        found
+    }
+}
@@ -0,0 +1,130 @@
+use anyhow::Result;
+use clap::Args;
+use std::path::PathBuf;
+
+use crate::commands::Verbosity;
+use crate::config::SiteConfig;
+use crate::markdown_meta::{BuildOptions, Index};
+use crate::watch::watch_content;
+
+#[derive(Debug, Args)]
+pub struct BuildArgs {
+    /// Markdown files or directories to convert, defaults to `build.content` in the config
+    pub paths: Vec<PathBuf>,
+    /// Directory the JSON files are written to, defaults to `build.output` in the config
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+    /// Convert every post even if it is unchanged since the last build
+    #[arg(short, long)]
+    pub force: bool,
+    /// Keep running and rebuild whenever a markdown file changes
+    #[arg(short, long)]
+    pub watch: bool,
+    /// Also publish posts marked `draft: true`
+    #[arg(long)]
+    pub drafts: bool,
+    /// Also publish posts dated after now
+    #[arg(long)]
+    pub future: bool,
+}
+
+pub fn run(args: BuildArgs, mut config: SiteConfig, verbosity: Verbosity) -> Result<()> {
+    if let Some(output) = args.output {
+        config.build.output = output;
+    }
+    let paths = config.content_roots(args.paths)?;
+    let build_options = BuildOptions {
+        force: args.force,
+        drafts: args.drafts,
+        future: args.future,
+    };
+    match Index::build(paths.clone(), &config, &build_options) {
+        Ok(index) => report(&index, &config, verbosity),
+        // a broken post should not stop the watcher from starting
+        Err(e) if args.watch => eprintln!("Build failed: {e:#}"),
+        Err(e) => return Err(e),
+    }
+    if !args.watch {
+        return Ok(());
+    }
+    if verbosity >= Verbosity::Normal {
+        println!("Watching for changes, press Ctrl-C to stop");
+    }
+    // the manifest keeps unchanged posts from being converted again
+    let build_options = BuildOptions {
+        force: false,
+        ..build_options
+    };
+    watch_content(&paths, &config.build.output, |changed| {
+        if verbosity >= Verbosity::Normal {
+            for path in changed {
+                println!("Changed: {}", path.display());
+            }
+        }
+        match Index::build(paths.clone(), &config, &build_options) {
+            Ok(index) => report(&index, &config, verbosity),
+            Err(e) => eprintln!("Build failed: {e:#}"),
+        }
+    })
+}
+
+pub fn report(index: &Index, config: &SiteConfig, verbosity: Verbosity) {
+    let summary = index.summary();
+    if verbosity >= Verbosity::Verbose {
+        for md in index.markdowns() {
+            println!("Built: {}", md.path().display());
+            if verbosity >= Verbosity::Debug {
+                println!("  tags: {}", md.metadata().tags.join(", "));
+            }
+        }
+        for path in &summary.unlisted {
+            println!("Unlisted: {}", path.display());
+        }
+        for path in &summary.skipped_drafts {
+            println!("Skipped draft: {}", path.display());
+        }
+        for path in &summary.scheduled {
+            println!("Scheduled: {}", path.display());
+        }
+        for path in &summary.unreferenced_assets {
+            println!("Unreferenced: {}", path.display());
+        }
+    }
+    if verbosity >= Verbosity::Normal {
+        let drafts = index
+            .markdowns()
+            .iter()
+            .filter(|md| md.metadata().draft)
+            .count();
+        let mut states = String::new();
+        if drafts > 0 {
+            states.push_str(&format!(", {drafts} draft(s) included"));
+        }
+        if !summary.skipped_drafts.is_empty() {
+            states.push_str(&format!(
+                ", {} draft(s) skipped",
+                summary.skipped_drafts.len()
+            ));
+        }
+        if !summary.scheduled.is_empty() {
+            states.push_str(&format!(", {} scheduled", summary.scheduled.len()));
+        }
+        if !summary.unlisted.is_empty() {
+            states.push_str(&format!(", {} unlisted", summary.unlisted.len()));
+        }
+        if !summary.unreferenced_assets.is_empty() {
+            states.push_str(&format!(
+                ", {} unreferenced file(s)",
+                summary.unreferenced_assets.len()
+            ));
+        }
+        println!(
+            "Built {} post(s) into {} ({} converted, {} unchanged, {} removed{states})",
+            index.markdowns().len(),
+            config.build.output.display(),
+            summary.converted,
+            summary.unchanged,
+            summary.removed
+        );
+    }
+}
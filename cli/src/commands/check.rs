@@ -0,0 +1,269 @@
+use anyhow::Result;
+use chrono::Utc;
+use clap::{Args, ValueEnum};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::PathBuf,
+};
+
+use crate::commands::Verbosity;
+use crate::config::SiteConfig;
+use crate::feed::TagDirs;
+use crate::front_matter::{FrontMatterError, FrontMatterErrorKind};
+use crate::links::LinkTarget;
+use crate::markdown_meta::{BuildOptions, Markdown, collect_markdown_paths};
+use crate::permalink;
+
+#[derive(Debug, Args)]
+pub struct CheckArgs {
+    /// Markdown files or directories to validate, defaults to `build.content` in the config
+    pub paths: Vec<PathBuf>,
+    /// `text` for people, `json` for CI annotations
+    #[arg(long, value_enum, default_value_t = ReportFormat::Text)]
+    pub format: ReportFormat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum ProblemKind {
+    MissingFile,
+    FrontMatter,
+    Date,
+    DuplicateSlug,
+    Permalink,
+    Tag,
+    BrokenLink,
+    Other,
+}
+
+/// One thing wrong with the content, `line` and `column` are 1-based.
+#[derive(Debug, Serialize)]
+struct Problem {
+    path: PathBuf,
+    line: Option<usize>,
+    column: Option<usize>,
+    kind: ProblemKind,
+    message: String,
+}
+
+#[derive(Debug, Serialize)]
+struct Report {
+    checked: usize,
+    problems: Vec<Problem>,
+}
+
+impl Problem {
+    fn from_error(path: PathBuf, err: &anyhow::Error) -> Self {
+        match err.downcast_ref::<FrontMatterError>() {
+            Some(e) => Self {
+                path,
+                line: e.line,
+                column: e.column,
+                kind: match e.kind {
+                    FrontMatterErrorKind::Syntax(_) => ProblemKind::FrontMatter,
+                    FrontMatterErrorKind::Date => ProblemKind::Date,
+                },
+                message: match e.kind {
+                    FrontMatterErrorKind::Syntax(format) => {
+                        format!("invalid {} front matter: {}", format.name(), e.message)
+                    }
+                    FrontMatterErrorKind::Date => e.message.clone(),
+                },
+            },
+            None => Self {
+                path,
+                line: None,
+                column: None,
+                kind: ProblemKind::Other,
+                message: format!("{:#}", err.root_cause()),
+            },
+        }
+    }
+
+    fn location(&self) -> String {
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => format!("{line}:{column}"),
+            (Some(line), None) => line.to_string(),
+            _ => "-".to_owned(),
+        }
+    }
+}
+
+pub fn run(args: CheckArgs, config: SiteConfig, verbosity: Verbosity) -> Result<()> {
+    let roots = config.content_roots(args.paths)?;
+    let mut problems = Vec::new();
+    // 1) 不存在的内容路径
+    let (roots, missing): (Vec<_>, Vec<_>) = roots.into_iter().partition(|root| root.exists());
+    for root in missing {
+        problems.push(Problem {
+            path: root,
+            line: None,
+            column: None,
+            kind: ProblemKind::MissingFile,
+            message: "no such file or directory".to_owned(),
+        });
+    }
+    // 2) 逐个解析, 出错也继续
+    let md_paths = collect_markdown_paths(&roots);
+    // 草稿和定时文章也算, build 同样不许它们和别的文章共用一个 permalink
+    let mut permalinks: HashMap<String, PathBuf> = HashMap::new();
+    // 和不带参数的 build 一样, 只有已发布的文章能被链接, 也只检查它们的链接
+    let now = Utc::now();
+    let timezone = config.build.timezone_offset();
+    let build_options = BuildOptions::default();
+    let mut published: Vec<(Markdown, String)> = Vec::new();
+    let mut tags: Vec<(&PathBuf, Vec<String>)> = Vec::new();
+    for path in &md_paths {
+        match Markdown::from_path(path.clone(), &config) {
+            Ok(md) => {
+                let metadata = md.metadata();
+                tags.push((path, metadata.tags.clone()));
+                match permalink::expand(&config.build.permalink, metadata.date.day, &metadata.slug)
+                {
+                    Err(message) => problems.push(Problem {
+                        path: path.clone(),
+                        line: None,
+                        column: None,
+                        kind: ProblemKind::Permalink,
+                        message,
+                    }),
+                    Ok(url) if permalinks.contains_key(&url) => {
+                        problems.push(Problem {
+                            path: path.clone(),
+                            line: None,
+                            column: None,
+                            kind: ProblemKind::DuplicateSlug,
+                            message: format!(
+                                "permalink `{url}` (slug `{}`) is already used by {}",
+                                metadata.slug,
+                                permalinks[&url].display()
+                            ),
+                        });
+                    }
+                    Ok(url) => {
+                        permalinks.insert(url.clone(), path.clone());
+                        if build_options.publishes(metadata, timezone, now) {
+                            published.push((md, url));
+                        }
+                    }
+                }
+                if verbosity >= Verbosity::Verbose && args.format == ReportFormat::Text {
+                    println!("OK: {}", path.display());
+                }
+            }
+            Err(e) => problems.push(Problem::from_error(path.clone(), &e)),
+        }
+    }
+    let mut tag_dirs = TagDirs::default();
+    for (path, tag) in tags
+        .iter()
+        .flat_map(|(path, tags)| tags.iter().map(move |tag| (*path, tag)))
+    {
+        if let Err(message) = tag_dirs.claim(tag, path) {
+            problems.push(Problem {
+                path: path.clone(),
+                line: None,
+                column: None,
+                kind: ProblemKind::Tag,
+                message,
+            });
+        }
+    }
+    // 3) 站内链接和本地文件, 和 build 的解析一样
+    let targets: HashMap<PathBuf, LinkTarget> = published
+        .iter()
+        .filter_map(|(md, url)| {
+            Some((
+                fs::canonicalize(md.path()).ok()?,
+                md.link_target(url.clone()),
+            ))
+        })
+        .collect();
+    for (md, _) in &published {
+        for link in md.links() {
+            if let Err(reason) = link.resolve(&targets) {
+                problems.push(Problem {
+                    path: md.path().to_path_buf(),
+                    line: Some(link.line),
+                    column: None,
+                    kind: ProblemKind::BrokenLink,
+                    message: format!("`{}` {reason}", link.written),
+                });
+            }
+        }
+        for asset in md.assets().iter().filter(|asset| !asset.source.is_file()) {
+            problems.push(Problem {
+                path: md.path().to_path_buf(),
+                line: Some(asset.line),
+                column: None,
+                kind: ProblemKind::MissingFile,
+                message: format!("`{}` points at a file that does not exist", asset.written),
+            });
+        }
+    }
+
+    let report = Report {
+        checked: md_paths.len(),
+        problems,
+    };
+    match args.format {
+        ReportFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+        ReportFormat::Text => print_report(&report, verbosity),
+    }
+    if !report.problems.is_empty() {
+        anyhow::bail!("{} problem(s) found", report.problems.len());
+    }
+    Ok(())
+}
+
+/// Problems grouped by file, then a one line summary.
+fn print_report(report: &Report, verbosity: Verbosity) {
+    let mut by_file: BTreeMap<&PathBuf, Vec<&Problem>> = BTreeMap::new();
+    for problem in &report.problems {
+        by_file.entry(&problem.path).or_default().push(problem);
+    }
+    for (path, problems) in &by_file {
+        println!("{}", path.display());
+        for problem in problems {
+            println!(
+                "  {:<8} {:<12} {}",
+                problem.location(),
+                kind_label(problem.kind),
+                problem.message
+            );
+        }
+    }
+    if verbosity >= Verbosity::Normal {
+        if report.problems.is_empty() {
+            println!("Checked {} file(s), no problems found", report.checked);
+        } else {
+            println!(
+                "Checked {} file(s), {} problem(s) in {} file(s)",
+                report.checked,
+                report.problems.len(),
+                by_file.len()
+            );
+        }
+    }
+}
+
+fn kind_label(kind: ProblemKind) -> &'static str {
+    match kind {
+        ProblemKind::MissingFile => "missing-file",
+        ProblemKind::FrontMatter => "front-matter",
+        ProblemKind::Date => "date",
+        ProblemKind::DuplicateSlug => "duplicate-slug",
+        ProblemKind::Permalink => "permalink",
+        ProblemKind::Tag => "tag",
+        ProblemKind::BrokenLink => "broken-link",
+        ProblemKind::Other => "error",
+    }
+}
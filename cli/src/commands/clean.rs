@@ -0,0 +1,63 @@
+use anyhow::{Context, Result};
+use clap::Args;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::commands::Verbosity;
+use crate::config::{DEFAULT_CONFIG_FILE, SiteConfig};
+
+#[derive(Debug, Args)]
+pub struct CleanArgs {
+    /// Directory to remove, defaults to `build.output` in the config
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+/// `config_file` is the `--config` given, if any.
+pub fn run(
+    args: CleanArgs,
+    config: SiteConfig,
+    config_file: Option<&Path>,
+    verbosity: Verbosity,
+) -> Result<()> {
+    let output = args.output.unwrap_or(config.build.output);
+    if !output.exists() {
+        if verbosity >= Verbosity::Verbose {
+            println!("Nothing to clean: {} does not exist", output.display());
+        }
+        return Ok(());
+    }
+    if !output.is_dir() {
+        anyhow::bail!("not a directory: {}", output.display());
+    }
+    // `output = "."` 或 `-o notes` 会把项目或者内容一起删掉
+    let canonical = fs::canonicalize(&output)
+        .with_context(|| format!("failed to resolve {}", output.display()))?;
+    let config_file = config_file.unwrap_or(Path::new(DEFAULT_CONFIG_FILE));
+    let config_dir = config_file
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let protected = [Path::new("."), config_dir]
+        .into_iter()
+        .chain(config.build.content.iter().map(PathBuf::as_path));
+    for dir in protected {
+        if let Ok(dir) = fs::canonicalize(dir)
+            && dir.starts_with(&canonical)
+        {
+            anyhow::bail!(
+                "refusing to remove {}, it contains {}",
+                output.display(),
+                dir.display()
+            );
+        }
+    }
+    fs::remove_dir_all(&output)
+        .with_context(|| format!("failed to remove {}", output.display()))?;
+    if verbosity >= Verbosity::Normal {
+        println!("Removed {}", output.display());
+    }
+    Ok(())
+}
@@ -0,0 +1,84 @@
+use anyhow::Result;
+use clap::{Args, Parser, Subcommand};
+use std::path::PathBuf;
+
+use crate::config::SiteConfig;
+
+pub mod build;
+pub mod check;
+pub mod clean;
+pub mod new;
+pub mod scheduled;
+pub mod serve;
+
+/// Static site generator: converts markdown notes into the JSON consumed by the web app.
+#[derive(Debug, Parser)]
+#[command(name = "sitegen", bin_name = "sitegen", version, about)]
+pub struct Cli {
+    #[command(flatten)]
+    pub global: GlobalArgs,
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Args)]
+pub struct GlobalArgs {
+    /// Config file, defaults to ./sitegen.toml when it exists
+    #[arg(short, long, global = true, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+    /// Print more details, repeat for even more (-vv)
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+    /// Only print errors
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Convert markdown under the given paths into JSON
+    Build(build::BuildArgs),
+    /// Parse every markdown file without writing anything
+    Check(check::CheckArgs),
+    /// Create a new post with a front matter skeleton
+    New(new::NewArgs),
+    /// List posts dated after now, soonest first
+    Scheduled(scheduled::ScheduledArgs),
+    /// Remove the output directory
+    Clean(clean::CleanArgs),
+    /// Build, watch and serve the site locally with live reload
+    Serve(serve::ServeArgs),
+}
+
+/// How chatty a command should be, derived from `-v` / `-q`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
+    Debug,
+}
+
+impl From<&GlobalArgs> for Verbosity {
+    fn from(args: &GlobalArgs) -> Self {
+        match (args.quiet, args.verbose) {
+            (true, _) => Verbosity::Quiet,
+            (false, 0) => Verbosity::Normal,
+            (false, 1) => Verbosity::Verbose,
+            (false, _) => Verbosity::Debug,
+        }
+    }
+}
+
+pub fn run(cli: Cli) -> Result<()> {
+    let verbosity = Verbosity::from(&cli.global);
+    let config = SiteConfig::load(cli.global.config.as_deref())?;
+    match cli.command {
+        Command::Build(args) => build::run(args, config, verbosity),
+        Command::Check(args) => check::run(args, config, verbosity),
+        Command::New(args) => new::run(args, config, verbosity),
+        Command::Scheduled(args) => scheduled::run(args, config, verbosity),
+        Command::Clean(args) => clean::run(args, config, cli.global.config.as_deref(), verbosity),
+        Command::Serve(args) => serve::run(args, config, verbosity),
+    }
+}
@@ -0,0 +1,78 @@
+use anyhow::{Context, Result};
+use clap::Args;
+use std::{fs, path::PathBuf};
+
+use crate::commands::Verbosity;
+use crate::config::SiteConfig;
+use crate::markdown_meta::FrontMatter;
+use crate::permalink::slugify;
+use crate::schedule::PostDate;
+
+#[derive(Debug, Args)]
+pub struct NewArgs {
+    /// Path of the markdown file to create (`.md` is appended when missing)
+    pub path: PathBuf,
+    /// Post title, defaults to the file name
+    #[arg(short, long)]
+    pub title: Option<String>,
+    /// Post author, defaults to `site.author` in the config
+    #[arg(short, long)]
+    pub author: Option<String>,
+    /// Tag to attach, can be repeated
+    #[arg(long = "tag")]
+    pub tags: Vec<String>,
+    /// Mark the post `draft: true` so builds skip it until `--drafts`
+    #[arg(short, long)]
+    pub draft: bool,
+    /// Overwrite the file if it already exists
+    #[arg(short, long)]
+    pub force: bool,
+}
+
+pub fn run(args: NewArgs, config: SiteConfig, verbosity: Verbosity) -> Result<()> {
+    let mut path = args.path;
+    if path.extension().is_none() {
+        path.set_extension("md");
+    }
+    if path.exists() && !args.force {
+        anyhow::bail!(
+            "{} already exists, pass --force to overwrite",
+            path.display()
+        );
+    }
+    let title = match args.title {
+        Some(title) => title,
+        None => path
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+    };
+    let front_matter = FrontMatter {
+        slug: slugify(&title),
+        title,
+        author: args
+            .author
+            .unwrap_or_else(|| config.default_author().to_owned()),
+        tags: if args.tags.is_empty() {
+            config.defaults.tags
+        } else {
+            args.tags
+        },
+        date: PostDate::from_day(chrono::Local::now().date_naive()),
+        summary: None,
+        draft: args.draft,
+        unlisted: false,
+        extra: serde_json::Value::Null,
+    };
+    let yaml = serde_yaml::to_string(&front_matter).context("serialize front matter failed")?;
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("create parent dir failed: {}", parent.display()))?;
+    }
+    fs::write(&path, format!("---\n{yaml}---\n\n"))
+        .with_context(|| format!("write to {} failed", path.display()))?;
+    if verbosity >= Verbosity::Normal {
+        println!("Created {}", path.display());
+    }
+    Ok(())
+}
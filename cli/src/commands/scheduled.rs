@@ -0,0 +1,47 @@
+use anyhow::Result;
+use clap::Args;
+use std::path::PathBuf;
+
+use crate::commands::Verbosity;
+use crate::config::SiteConfig;
+use crate::markdown_meta::{Markdown, collect_markdown_paths};
+
+#[derive(Debug, Args)]
+pub struct ScheduledArgs {
+    /// Markdown files or directories to look at, defaults to `build.content` in the config
+    pub paths: Vec<PathBuf>,
+}
+
+pub fn run(args: ScheduledArgs, config: SiteConfig, verbosity: Verbosity) -> Result<()> {
+    let md_paths = collect_markdown_paths(&config.content_roots(args.paths)?);
+    let now = chrono::Utc::now();
+    let timezone = config.build.timezone_offset();
+    let mut scheduled = Vec::new();
+    for path in md_paths {
+        let md = Markdown::from_path(path, &config)?;
+        let publish_at = md.metadata().date.publish_at(timezone);
+        if publish_at > now {
+            scheduled.push((publish_at, md));
+        }
+    }
+    // 最早发布的排在前面
+    scheduled.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.path().cmp(b.1.path())));
+    for (publish_at, md) in &scheduled {
+        let draft = if md.metadata().draft { " (draft)" } else { "" };
+        // 和文章日期一样, 没写时区的按 `build.timezone` 显示
+        let when = match timezone {
+            Some(offset) => publish_at.with_timezone(&offset).fixed_offset(),
+            None => publish_at.with_timezone(&chrono::Local).fixed_offset(),
+        };
+        println!(
+            "{}  {}{draft}  {}",
+            when.format("%Y-%m-%d %H:%M %:z"),
+            md.metadata().title,
+            md.path().display()
+        );
+    }
+    if verbosity >= Verbosity::Normal {
+        println!("{} post(s) scheduled", scheduled.len());
+    }
+    Ok(())
+}
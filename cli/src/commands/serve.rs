@@ -0,0 +1,229 @@
+use anyhow::Result;
+use clap::Args;
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Mutex},
+    thread,
+};
+use tiny_http::{Header, Request, Response, Server};
+
+use crate::commands::{Verbosity, build};
+use crate::config::SiteConfig;
+use crate::links::percent_decode;
+use crate::markdown_meta::{BuildOptions, Index};
+use crate::watch::watch_content;
+
+/// Built next to `build.output`, so localhost URLs never end up in the deployable output
+/// and switching between `build` and `serve` does not invalidate either manifest.
+const PREVIEW_DIR: &str = ".sitegen-preview";
+
+/// Endpoint browsers subscribe to for reload notifications.
+const EVENTS_PATH: &str = "/__sitegen/events";
+
+/// Injected into every served HTML page: points the web app at this server for content
+/// and reloads the page after each rebuild.
+const LIVE_RELOAD_SNIPPET: &str = r#"<meta name="content-base-url" content="/" />
+<script>new EventSource("/__sitegen/events").onmessage = () => location.reload();</script>
+"#;
+
+#[derive(Debug, Args)]
+pub struct ServeArgs {
+    /// Markdown files or directories to watch, defaults to `build.content` in the config
+    pub paths: Vec<PathBuf>,
+    /// Directory to build into and serve, defaults to `.sitegen-preview` next to `build.output`,
+    /// or `build.output` itself with `--no-watch`
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+    /// Directory holding the Trunk-built web app
+    #[arg(long, default_value = "web/dist")]
+    pub web: PathBuf,
+    /// Address to bind
+    #[arg(long, default_value = "127.0.0.1")]
+    pub host: String,
+    /// Port to listen on
+    #[arg(short, long, default_value_t = 8000)]
+    pub port: u16,
+    /// Serve what is already built instead of building and watching the content
+    #[arg(long)]
+    pub no_watch: bool,
+    /// Also publish posts marked `draft: true`
+    #[arg(long)]
+    pub drafts: bool,
+    /// Also publish posts dated after now
+    #[arg(long)]
+    pub future: bool,
+}
+
+/// Open event streams of connected browsers.
+#[derive(Clone, Default)]
+struct ReloadClients(Arc<Mutex<Vec<Box<dyn Write + Send>>>>);
+
+impl ReloadClients {
+    fn subscribe(&self, request: Request) {
+        let mut writer = request.into_writer();
+        let head = "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: keep-alive\r\n\r\n";
+        if writer
+            .write_all(head.as_bytes())
+            .and_then(|_| writer.flush())
+            .is_ok()
+        {
+            self.0.lock().expect("reload clients poisoned").push(writer);
+        }
+    }
+
+    /// Tell every browser to reload, forgetting the ones that went away.
+    fn broadcast(&self) {
+        let mut clients = self.0.lock().expect("reload clients poisoned");
+        clients.retain_mut(|w| {
+            w.write_all(b"data: reload\n\n")
+                .and_then(|_| w.flush())
+                .is_ok()
+        });
+    }
+}
+
+pub fn run(args: ServeArgs, mut config: SiteConfig, verbosity: Verbosity) -> Result<()> {
+    let addr = format!("{}:{}", args.host, args.port);
+    config.build.output = match args.output {
+        Some(output) => output,
+        None if args.no_watch => config.build.output,
+        None => config.build.output.with_file_name(PREVIEW_DIR),
+    };
+    let clients = ReloadClients::default();
+    if !args.no_watch {
+        // links in prerendered pages and feeds should stay on this server
+        config.site.base_url = format!("http://{addr}/");
+        let paths = config.content_roots(args.paths)?;
+        let build_options = BuildOptions {
+            drafts: args.drafts,
+            future: args.future,
+            ..Default::default()
+        };
+        let index = Index::build(paths.clone(), &config, &build_options)?;
+        build::report(&index, &config, verbosity);
+        let config = config.clone();
+        let clients = clients.clone();
+        thread::spawn(move || {
+            let result = watch_content(&paths, &config.build.output, |_| {
+                match Index::build(paths.clone(), &config, &build_options) {
+                    Ok(index) => {
+                        build::report(&index, &config, verbosity);
+                        clients.broadcast();
+                    }
+                    Err(e) => eprintln!("Build failed: {e:#}"),
+                }
+            });
+            if let Err(e) = result {
+                eprintln!("Watcher stopped: {e:#}");
+            }
+        });
+    }
+    let output = config.build.output;
+    if !output.is_dir() {
+        anyhow::bail!(
+            "{} is not a directory, run `sitegen build` first",
+            output.display()
+        );
+    }
+    if !args.web.is_dir() && verbosity >= Verbosity::Normal {
+        eprintln!(
+            "Web app not found at {}, run `trunk build` in web/ to preview it; serving content only",
+            args.web.display()
+        );
+    }
+    let server = Server::http(&addr).map_err(|e| anyhow::anyhow!("bind {addr} failed: {e}"))?;
+    if verbosity >= Verbosity::Normal {
+        println!("Serving {} at http://{addr}/", output.display());
+    }
+    for request in server.incoming_requests() {
+        let url = request.url().to_owned();
+        if url == EVENTS_PATH {
+            clients.subscribe(request);
+            continue;
+        }
+        // content first, then the web app, then the app shell for client side routes
+        let found = resolve(&output, &url)
+            .or_else(|| resolve(&args.web, &url))
+            .or_else(|| {
+                let is_route = Path::new(&url).extension().is_none();
+                is_route.then(|| resolve(&args.web, "/")).flatten()
+            });
+        let response = match found {
+            Some(path) => file_response(&path),
+            None => Response::from_string("Not Found")
+                .with_status_code(404)
+                .boxed(),
+        };
+        if verbosity >= Verbosity::Verbose {
+            println!("{} {url} {}", request.method(), response.status_code().0);
+        }
+        if let Err(e) = request.respond(response) {
+            eprintln!("respond to {url} failed: {e}");
+        }
+    }
+    Ok(())
+}
+
+fn file_response(path: &Path) -> tiny_http::ResponseBox {
+    let is_html = path.extension().is_some_and(|ext| ext == "html");
+    if is_html {
+        return match fs::read_to_string(path) {
+            Ok(html) => Response::from_string(inject_live_reload(&html))
+                .with_header(content_type(path))
+                .boxed(),
+            Err(_) => Response::from_string("Not Found")
+                .with_status_code(404)
+                .boxed(),
+        };
+    }
+    match File::open(path) {
+        Ok(file) => Response::from_file(file)
+            .with_header(content_type(path))
+            .boxed(),
+        Err(_) => Response::from_string("Not Found")
+            .with_status_code(404)
+            .boxed(),
+    }
+}
+
+fn inject_live_reload(html: &str) -> String {
+    match html.find("</head>") {
+        Some(pos) => format!("{}{LIVE_RELOAD_SNIPPET}{}", &html[..pos], &html[pos..]),
+        None => format!("{LIVE_RELOAD_SNIPPET}{html}"),
+    }
+}
+
+/// Map a request URL onto a file under `root`, refusing anything that escapes it.
+fn resolve(root: &Path, url: &str) -> Option<PathBuf> {
+    // CJK slugs and tag directories arrive percent-encoded
+    let path = percent_decode(url.split(['?', '#']).next().unwrap_or(""))?;
+    let rel = Path::new(path.trim_start_matches('/'));
+    if rel.components().any(|c| !matches!(c, Component::Normal(_))) {
+        return None;
+    }
+    let mut full = root.join(rel);
+    if full.is_dir() {
+        full.push("index.html");
+    }
+    full.is_file().then_some(full)
+}
+
+fn content_type(path: &Path) -> Header {
+    let mime = match path.extension().and_then(|s| s.to_str()) {
+        Some("html") => "text/html; charset=utf-8",
+        Some("json") => "application/json",
+        Some("js") => "text/javascript",
+        Some("css") => "text/css",
+        Some("wasm") => "application/wasm",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("webp") => "image/webp",
+        Some("avif") => "image/avif",
+        Some("svg") => "image/svg+xml",
+        Some("xml") => "application/xml",
+        _ => "application/octet-stream",
+    };
+    Header::from_bytes("Content-Type", mime).expect("static header is valid")
+}
@@ -0,0 +1,364 @@
+use anyhow::{Context, Result};
+use chrono::FixedOffset;
+use comrak::Options;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::highlight;
+use crate::permalink;
+
+pub const DEFAULT_CONFIG_FILE: &str = "sitegen.toml";
+
+/// Contents of `sitegen.toml`. Every section is optional, a missing file behaves like an empty one.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SiteConfig {
+    pub site: SiteSection,
+    pub build: BuildSection,
+    pub defaults: DefaultsSection,
+    pub markdown: MarkdownSection,
+    pub highlight: HighlightSection,
+    pub feed: FeedSection,
+    pub sitemap: SitemapSection,
+    pub robots: RobotsSection,
+    pub prerender: PrerenderSection,
+    pub images: ImagesSection,
+}
+
+/// Site wide metadata, also dumped into `index.json` for the web app.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SiteSection {
+    pub title: String,
+    pub author: String,
+    pub base_url: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BuildSection {
+    /// Content roots used when no path is given on the command line
+    pub content: Vec<PathBuf>,
+    pub output: PathBuf,
+    /// UTC offset such as `+08:00` for post dates that carry none, defaults to the machine's zone
+    pub timezone: Option<String>,
+    /// Post address built from `{year}`, `{month}`, `{day}` and `{slug}`
+    pub permalink: String,
+}
+
+impl Default for BuildSection {
+    fn default() -> Self {
+        Self {
+            content: Vec::new(),
+            output: PathBuf::from("dist"),
+            timezone: None,
+            permalink: permalink::DEFAULT_PERMALINK.to_owned(),
+        }
+    }
+}
+
+/// Front matter values for posts that leave them out.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DefaultsSection {
+    /// Falls back to `site.author`
+    pub author: Option<String>,
+    pub tags: Vec<String>,
+}
+
+/// The subset of comrak options worth tuning per site.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MarkdownSection {
+    pub strikethrough: bool,
+    pub table: bool,
+    pub autolink: bool,
+    pub tasklist: bool,
+    pub superscript: bool,
+    pub footnotes: bool,
+    pub description_lists: bool,
+    pub cjk_friendly_emphasis: bool,
+    /// Prefix for the slug `id` every heading gets
+    pub header_ids: Option<String>,
+    pub smart: bool,
+    pub hardbreaks: bool,
+    /// `$..$` and `$$..$$` math, rendered to MathML at build time
+    pub math: bool,
+    /// Pass raw HTML in markdown through instead of replacing it with a comment
+    pub unsafe_html: bool,
+}
+
+/// Build-time highlighting of fenced code blocks.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HighlightSection {
+    pub enabled: bool,
+    pub mode: HighlightMode,
+    /// syntect theme name, the light one in `css` mode
+    pub theme: String,
+    /// Only used in `css` mode, applied under `prefers-color-scheme: dark`
+    pub dark_theme: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HighlightMode {
+    /// Class names in the HTML plus a generated `highlight.css`
+    #[default]
+    Css,
+    /// Colors baked into `style` attributes
+    Inline,
+}
+
+impl Default for HighlightSection {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            mode: HighlightMode::Css,
+            theme: "InspiredGitHub".to_owned(),
+            dark_theme: Some("base16-ocean.dark".to_owned()),
+        }
+    }
+}
+
+/// Atom, RSS and JSON feeds written next to `index.json`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeedSection {
+    pub enabled: bool,
+    /// Newest posts kept in each feed
+    pub items: usize,
+    /// Also write a feed per tag under `tags/<tag>/`
+    pub tags: bool,
+}
+
+impl Default for FeedSection {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            items: 20,
+            tags: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SitemapSection {
+    pub enabled: bool,
+}
+
+impl Default for SitemapSection {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+/// `robots.txt`, it also points crawlers at the sitemap.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RobotsSection {
+    pub enabled: bool,
+    pub user_agent: String,
+    /// Site relative paths crawlers should skip, nothing is disallowed by default
+    pub disallow: Vec<String>,
+}
+
+impl Default for RobotsSection {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            user_agent: "*".to_owned(),
+            disallow: Vec::new(),
+        }
+    }
+}
+
+/// Static HTML for every post, the archive and each tag, for crawlers and readers without wasm.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PrerenderSection {
+    pub enabled: bool,
+    /// Trunk-built `index.html` the pages are injected into so the web app boots on top of them
+    pub shell: Option<PathBuf>,
+}
+
+impl Default for PrerenderSection {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            shell: None,
+        }
+    }
+}
+
+/// Resized AVIF and WebP copies of local PNG, JPEG and WebP images, with a blurred placeholder.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ImagesSection {
+    pub enabled: bool,
+    /// Variant widths in pixels, an image is never scaled up past its own width
+    pub widths: Vec<u32>,
+    /// Offered in this order, browsers take the first one they support
+    pub formats: Vec<ImageFormat>,
+    /// Encoder quality, 1 to 100
+    pub quality: u8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageFormat {
+    Avif,
+    Webp,
+}
+
+impl Default for ImagesSection {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            widths: vec![480, 960, 1600],
+            formats: vec![ImageFormat::Avif, ImageFormat::Webp],
+            quality: 70,
+        }
+    }
+}
+
+impl ImageFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Avif => "avif",
+            Self::Webp => "webp",
+        }
+    }
+
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            Self::Avif => "image/avif",
+            Self::Webp => "image/webp",
+        }
+    }
+}
+
+impl SiteConfig {
+    /// Load `path`, or `sitegen.toml` in the working directory when `path` is `None`.
+    /// Only an explicitly requested file is required to exist.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let (path, required) = match path {
+            Some(p) => (p, true),
+            None => (Path::new(DEFAULT_CONFIG_FILE), false),
+        };
+        if !path.exists() {
+            if required {
+                anyhow::bail!("config file does not exist: {}", path.display());
+            }
+            return Ok(Self::default());
+        }
+        let input = fs::read_to_string(path)
+            .with_context(|| format!("failed to read: {}", path.display()))?;
+        let mut config: SiteConfig = toml::from_str(&input)
+            .with_context(|| format!("Invalid config file: {}", path.display()))?;
+        for theme in std::iter::once(&config.highlight.theme).chain(&config.highlight.dark_theme) {
+            if !highlight::theme_exists(theme) {
+                anyhow::bail!("unknown highlight theme `{theme}` in {}", path.display());
+            }
+        }
+        if let Some(timezone) = &config.build.timezone
+            && timezone.parse::<FixedOffset>().is_err()
+        {
+            anyhow::bail!(
+                "invalid `build.timezone` `{timezone}` in {}, expected an offset like +08:00",
+                path.display()
+            );
+        }
+        if let Err(e) = permalink::validate(&config.build.permalink) {
+            anyhow::bail!("invalid `build.permalink` in {}: {e}", path.display());
+        }
+        let images = &config.images;
+        if images.enabled
+            && (images.widths.is_empty() || images.widths.contains(&0) || images.formats.is_empty())
+        {
+            anyhow::bail!(
+                "`images.widths` and `images.formats` must not be empty and widths must be above 0 in {}",
+                path.display()
+            );
+        }
+        if !(1..=100).contains(&images.quality) {
+            anyhow::bail!(
+                "invalid `images.quality` {} in {}, expected 1 to 100",
+                images.quality,
+                path.display()
+            );
+        }
+        // relative paths in the file are relative to the file itself
+        if let Some(base) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            for root in &mut config.build.content {
+                if root.is_relative() {
+                    *root = base.join(&*root);
+                }
+            }
+            if config.build.output.is_relative() {
+                config.build.output = base.join(&config.build.output);
+            }
+            if let Some(shell) = config.prerender.shell.as_mut().filter(|p| p.is_relative()) {
+                *shell = base.join(&*shell);
+            }
+        }
+        Ok(config)
+    }
+
+    /// Everything in the config that can change a post's output, used to invalidate the build manifest.
+    pub fn fingerprint(&self) -> String {
+        format!("{}:{:?}", env!("CARGO_PKG_VERSION"), self)
+    }
+
+    /// Command line paths win over the configured content roots.
+    pub fn content_roots(&self, cli_paths: Vec<PathBuf>) -> Result<Vec<PathBuf>> {
+        if !cli_paths.is_empty() {
+            return Ok(cli_paths);
+        }
+        if self.build.content.is_empty() {
+            anyhow::bail!(
+                "no content path given and `build.content` is empty in {DEFAULT_CONFIG_FILE}"
+            );
+        }
+        Ok(self.build.content.clone())
+    }
+
+    /// Author for posts whose front matter has none.
+    pub fn default_author(&self) -> &str {
+        self.defaults.author.as_deref().unwrap_or(&self.site.author)
+    }
+}
+
+impl BuildSection {
+    /// Offset for post dates without one, `None` means the local zone.
+    pub fn timezone_offset(&self) -> Option<FixedOffset> {
+        self.timezone.as_deref().and_then(|tz| tz.parse().ok())
+    }
+}
+
+impl MarkdownSection {
+    pub fn comrak_options(&self) -> Options<'static> {
+        let mut options = Options::default();
+        options.extension.front_matter_delimiter = Some("---".to_owned());
+        options.extension.strikethrough = self.strikethrough;
+        options.extension.table = self.table;
+        options.extension.autolink = self.autolink;
+        options.extension.tasklist = self.tasklist;
+        options.extension.superscript = self.superscript;
+        options.extension.footnotes = self.footnotes;
+        options.extension.description_lists = self.description_lists;
+        options.extension.cjk_friendly_emphasis = self.cjk_friendly_emphasis;
+        options.extension.header_ids = self.header_ids.clone();
+        options.extension.math_dollars = self.math;
+        options.parse.smart = self.smart;
+        options.render.hardbreaks = self.hardbreaks;
+        options.render.r#unsafe = self.unsafe_html;
+        // lets the highlighter see `{3,5-7}` after the language
+        options.render.full_info_string = true;
+        options
+    }
+}
@@ -0,0 +1,72 @@
+use anyhow::{Context, Result};
+use comrak::{
+    Arena, Options,
+    nodes::{AstNode, NodeValue},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::stats::plain_text;
+
+/// A short lead-in shown under the title in listings.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Excerpt {
+    pub text: String,
+    /// Rendered without raw HTML, whatever `markdown.unsafe_html` says
+    pub html: String,
+}
+
+impl Excerpt {
+    /// Taken from, in order: the `summary` front matter field, everything before a
+    /// `<!-- more -->` line, or the first paragraph.
+    pub fn from_ast<'a>(
+        root: &'a AstNode<'a>,
+        summary: Option<&str>,
+        options: &Options,
+    ) -> Result<Self> {
+        let mut options = options.clone();
+        options.render.r#unsafe = false;
+        if let Some(summary) = summary {
+            let arena = Arena::new();
+            let summary_root = comrak::parse_document(&arena, summary, &options);
+            return Self::from_blocks(summary_root.children(), &options);
+        }
+        match root.children().position(is_more_marker) {
+            Some(end) => Self::from_blocks(
+                root.children()
+                    .take(end)
+                    .filter(|node| !matches!(node.data.borrow().value, NodeValue::FrontMatter(_))),
+                &options,
+            ),
+            None => Self::from_blocks(
+                root.children()
+                    .find(|node| matches!(node.data.borrow().value, NodeValue::Paragraph)),
+                &options,
+            ),
+        }
+    }
+
+    fn from_blocks<'a>(
+        blocks: impl IntoIterator<Item = &'a AstNode<'a>>,
+        options: &Options,
+    ) -> Result<Self> {
+        let mut excerpt = Self::default();
+        let mut text = String::new();
+        for block in blocks {
+            comrak::format_html(block, options, &mut excerpt.html)
+                .context("render excerpt failed")?;
+            text.push_str(&plain_text(block));
+        }
+        excerpt.text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        Ok(excerpt)
+    }
+}
+
+fn is_more_marker<'a>(node: &'a AstNode<'a>) -> bool {
+    match &node.data.borrow().value {
+        NodeValue::HtmlBlock(block) => {
+            let marker: String = block.literal.split_whitespace().collect();
+            marker.eq_ignore_ascii_case("<!--more-->")
+        }
+        _ => false,
+    }
+}
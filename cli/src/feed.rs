@@ -0,0 +1,282 @@
+use anyhow::{Context, Result};
+use chrono::NaiveDate;
+use serde_json::json;
+use std::{collections::HashMap, fmt::Write, fs, path::Path};
+
+use crate::config::{FeedSection, SiteSection};
+use crate::permalink::slugify;
+
+pub const ATOM_FILE: &str = "feed.xml";
+pub const RSS_FILE: &str = "rss.xml";
+pub const JSON_FEED_FILE: &str = "feed.json";
+/// Tag pages and their feeds live under `tags/<tag>/` in the output dir.
+pub const TAG_DIR: &str = "tags";
+
+/// One post as it appears in a feed, newest first.
+pub struct FeedEntry<'a> {
+    pub title: &'a str,
+    pub author: &'a str,
+    /// Site relative, joined to `site.base_url`
+    pub url: String,
+    pub date: NaiveDate,
+    pub summary: &'a str,
+    pub content: &'a str,
+    pub tags: &'a [String],
+}
+
+/// A feed title plus the dir (relative to the output dir) its three files are written to.
+pub struct Feed<'a> {
+    pub title: String,
+    pub dir: String,
+    pub entries: Vec<&'a FeedEntry<'a>>,
+}
+
+impl Feed<'_> {
+    /// Write `feed.xml`, `rss.xml` and `feed.json`, keeping at most `feed.items` entries.
+    pub fn write(&self, dist_dir: &Path, site: &SiteSection, config: &FeedSection) -> Result<()> {
+        let dir = dist_dir.join(&self.dir);
+        fs::create_dir_all(&dir).with_context(|| format!("failed to create {}", dir.display()))?;
+        let entries = &self.entries[..self.entries.len().min(config.items)];
+        for (file, body) in [
+            (ATOM_FILE, self.atom(site, entries)),
+            (RSS_FILE, self.rss(site, entries)),
+            (JSON_FEED_FILE, self.json(site, entries)?),
+        ] {
+            let path = dir.join(file);
+            fs::write(&path, body)
+                .with_context(|| format!("write to {} failed", path.display()))?;
+        }
+        Ok(())
+    }
+
+    fn feed_url(&self, site: &SiteSection, file: &str) -> String {
+        absolute_url(site, &format!("{}{file}", dir_prefix(&self.dir)))
+    }
+
+    fn atom(&self, site: &SiteSection, entries: &[&FeedEntry]) -> String {
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+        xml.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+        let self_url = self.feed_url(site, ATOM_FILE);
+        let updated = entries
+            .first()
+            .map_or_else(|| chrono::Utc::now().date_naive(), |entry| entry.date);
+        let _ = writeln!(xml, "  <title>{}</title>", escape_xml(&self.title));
+        let _ = writeln!(xml, "  <id>{}</id>", escape_xml(&self_url));
+        let _ = writeln!(
+            xml,
+            "  <link rel=\"self\" href=\"{}\"/>",
+            escape_xml(&self_url)
+        );
+        let _ = writeln!(
+            xml,
+            "  <link href=\"{}\"/>",
+            escape_xml(&absolute_url(site, ""))
+        );
+        let _ = writeln!(xml, "  <updated>{}</updated>", rfc3339(updated));
+        if !site.author.is_empty() {
+            let _ = writeln!(
+                xml,
+                "  <author><name>{}</name></author>",
+                escape_xml(&site.author)
+            );
+        }
+        for entry in entries {
+            let url = absolute_url(site, &entry.url);
+            xml.push_str("  <entry>\n");
+            let _ = writeln!(xml, "    <title>{}</title>", escape_xml(entry.title));
+            let _ = writeln!(xml, "    <id>{}</id>", escape_xml(&url));
+            let _ = writeln!(xml, "    <link href=\"{}\"/>", escape_xml(&url));
+            let _ = writeln!(xml, "    <updated>{}</updated>", rfc3339(entry.date));
+            let _ = writeln!(
+                xml,
+                "    <author><name>{}</name></author>",
+                escape_xml(entry.author)
+            );
+            for tag in entry.tags {
+                let _ = writeln!(xml, "    <category term=\"{}\"/>", escape_xml(tag));
+            }
+            let _ = writeln!(xml, "    <summary>{}</summary>", escape_xml(entry.summary));
+            let _ = writeln!(
+                xml,
+                "    <content type=\"html\">{}</content>",
+                escape_xml(entry.content)
+            );
+            xml.push_str("  </entry>\n");
+        }
+        xml.push_str("</feed>\n");
+        xml
+    }
+
+    fn rss(&self, site: &SiteSection, entries: &[&FeedEntry]) -> String {
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+        xml.push_str(
+            "<rss version=\"2.0\" xmlns:atom=\"http://www.w3.org/2005/Atom\" \
+             xmlns:content=\"http://purl.org/rss/1.0/modules/content/\">\n",
+        );
+        xml.push_str("  <channel>\n");
+        let _ = writeln!(xml, "    <title>{}</title>", escape_xml(&self.title));
+        let _ = writeln!(
+            xml,
+            "    <link>{}</link>",
+            escape_xml(&absolute_url(site, ""))
+        );
+        let _ = writeln!(
+            xml,
+            "    <description>{}</description>",
+            escape_xml(&self.title)
+        );
+        let _ = writeln!(
+            xml,
+            "    <atom:link href=\"{}\" rel=\"self\" type=\"application/rss+xml\"/>",
+            escape_xml(&self.feed_url(site, RSS_FILE))
+        );
+        for entry in entries {
+            let url = absolute_url(site, &entry.url);
+            xml.push_str("    <item>\n");
+            let _ = writeln!(xml, "      <title>{}</title>", escape_xml(entry.title));
+            let _ = writeln!(xml, "      <link>{}</link>", escape_xml(&url));
+            let _ = writeln!(
+                xml,
+                "      <guid isPermaLink=\"true\">{}</guid>",
+                escape_xml(&url)
+            );
+            let _ = writeln!(xml, "      <pubDate>{}</pubDate>", rfc2822(entry.date));
+            for tag in entry.tags {
+                let _ = writeln!(xml, "      <category>{}</category>", escape_xml(tag));
+            }
+            let _ = writeln!(
+                xml,
+                "      <description>{}</description>",
+                escape_xml(entry.summary)
+            );
+            let _ = writeln!(
+                xml,
+                "      <content:encoded>{}</content:encoded>",
+                escape_xml(entry.content)
+            );
+            xml.push_str("    </item>\n");
+        }
+        xml.push_str("  </channel>\n</rss>\n");
+        xml
+    }
+
+    fn json(&self, site: &SiteSection, entries: &[&FeedEntry]) -> Result<String> {
+        let items: Vec<_> = entries
+            .iter()
+            .map(|entry| {
+                let url = absolute_url(site, &entry.url);
+                json!({
+                    "id": url,
+                    "url": url,
+                    "title": entry.title,
+                    "summary": entry.summary,
+                    "content_html": entry.content,
+                    "date_published": rfc3339(entry.date),
+                    "authors": [{ "name": entry.author }],
+                    "tags": entry.tags,
+                })
+            })
+            .collect();
+        let feed = json!({
+            "version": "https://jsonfeed.org/version/1.1",
+            "title": self.title,
+            "home_page_url": absolute_url(site, ""),
+            "feed_url": self.feed_url(site, JSON_FEED_FILE),
+            "items": items,
+        });
+        serde_json::to_string_pretty(&feed).context("serialize json feed failed")
+    }
+}
+
+/// `tags/<slug>`, with the tag slugified like a post title.
+pub fn tag_dir(tag: &str) -> String {
+    format!("{TAG_DIR}/{}", slugify(tag))
+}
+
+/// Which tag, first seen in which post, has each tag dir, so two tags never share one.
+#[derive(Default)]
+pub struct TagDirs<'a> {
+    owners: HashMap<String, (&'a str, &'a Path)>,
+}
+
+impl<'a> TagDirs<'a> {
+    /// Fails for a tag without letters or digits, or whose dir a different tag already has.
+    pub fn claim(&mut self, tag: &'a str, post: &'a Path) -> Result<(), String> {
+        if slugify(tag).is_empty() {
+            return Err(format!(
+                "tag `{tag}` has no letters or digits to name its page after"
+            ));
+        }
+        let dir = tag_dir(tag);
+        match self.owners.get(&dir) {
+            Some((other, other_post)) if *other != tag => Err(format!(
+                "tags `{other}` ({}) and `{tag}` would both be written to `{dir}/`, use the same spelling for both",
+                other_post.display()
+            )),
+            Some(_) => Ok(()),
+            None => {
+                self.owners.insert(dir, (tag, post));
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Feeds and the sitemap need absolute URLs, they are skipped without an `http(s)://` base URL.
+pub fn has_absolute_base_url(site: &SiteSection) -> bool {
+    let base = site.base_url.to_ascii_lowercase();
+    base.starts_with("https://") || base.starts_with("http://")
+}
+
+/// Join a site relative path to `site.base_url`, or to `/` when no base URL is configured.
+pub fn absolute_url(site: &SiteSection, path: &str) -> String {
+    let base = site.base_url.trim_end_matches('/');
+    format!("{base}/{}", path.trim_start_matches('/'))
+}
+
+fn dir_prefix(dir: &str) -> String {
+    if dir.is_empty() {
+        String::new()
+    } else {
+        format!("{}/", dir.trim_end_matches('/'))
+    }
+}
+
+fn rfc3339(date: NaiveDate) -> String {
+    format!("{}T00:00:00Z", date.format("%Y-%m-%d"))
+}
+
+fn rfc2822(date: NaiveDate) -> String {
+    date.and_hms_opt(0, 0, 0)
+        .unwrap_or_default()
+        .and_utc()
+        .to_rfc2822()
+}
+
+pub fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tag_dirs_use_post_slugs() {
+        assert_eq!(tag_dir("Rust Async"), "tags/rust-async");
+        assert_eq!(tag_dir("C++ / 入门"), "tags/c-入门");
+    }
+
+    #[test]
+    fn rejects_tags_sharing_a_dir() {
+        let mut dirs = TagDirs::default();
+        assert!(dirs.claim("Rust", Path::new("a.md")).is_ok());
+        assert!(dirs.claim("Rust", Path::new("b.md")).is_ok());
+        let err = dirs.claim("rust", Path::new("b.md")).unwrap_err();
+        assert!(err.contains("`Rust` (a.md) and `rust`"), "{err}");
+        assert!(dirs.claim("++", Path::new("c.md")).is_err());
+    }
+}
@@ -0,0 +1,304 @@
+use serde::de::{DeserializeOwned, IgnoredAny};
+use std::{collections::BTreeMap, fmt};
+
+/// How a post's front matter is written, told apart by the first line of the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrontMatterFormat {
+    /// Between `---` lines
+    Yaml,
+    /// Between `+++` lines, as Hugo writes it
+    Toml,
+    /// A JSON object opening the file, either on a line of its own `{` or parsing as one,
+    /// so templating like `{{< figure >}}` or `{% raw %}` stays markdown
+    Json,
+}
+
+impl FrontMatterFormat {
+    pub fn detect(input: &str) -> Option<Self> {
+        let first = input.lines().next()?.trim_end();
+        match first {
+            "---" => Some(Self::Yaml),
+            "+++" => Some(Self::Toml),
+            "{" => Some(Self::Json),
+            _ if first.starts_with('{') && starts_with_json_object(input) => Some(Self::Json),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Yaml => "YAML",
+            Self::Toml => "TOML",
+            Self::Json => "JSON",
+        }
+    }
+
+    /// Fence comrak cuts the front matter at, JSON has none and is taken off by `split_json`.
+    pub fn delimiter(self) -> Option<&'static str> {
+        match self {
+            Self::Yaml => Some("---"),
+            Self::Toml => Some("+++"),
+            Self::Json => None,
+        }
+    }
+
+    /// The front matter block comrak hands back, without its fences.
+    pub fn strip_fences(self, block: &str) -> &str {
+        let block = block.trim();
+        match self.delimiter() {
+            Some(fence) => block
+                .trim_start_matches(fence)
+                .trim_end_matches(fence)
+                .trim(),
+            None => block,
+        }
+    }
+
+    pub fn parse<T: DeserializeOwned>(self, text: &str) -> Result<T, FrontMatterError> {
+        let syntax = |message: String, at: Option<(usize, usize)>| FrontMatterError {
+            kind: FrontMatterErrorKind::Syntax(self),
+            line: at.map(|(line, _)| line),
+            column: at.map(|(_, column)| column),
+            message,
+        };
+        match self {
+            // 空的 YAML 文档不是 mapping
+            Self::Yaml => serde_yaml::from_str(if text.trim().is_empty() { "{}" } else { text })
+                .map_err(|e| {
+                    let at = e.location().map(|at| (at.line(), at.column()));
+                    syntax(without_position(e.to_string(), at), at)
+                }),
+            Self::Toml => {
+                let table: toml::Table = toml::from_str(text).map_err(|e| {
+                    let at = e.span().map(|span| line_column(text, span.start));
+                    syntax(e.message().to_owned(), at)
+                })?;
+                serde_json::from_value(toml_to_json(toml::Value::Table(table)))
+                    .map_err(|e| syntax(e.to_string(), None))
+            }
+            Self::Json => serde_json::from_str(text).map_err(|e| {
+                let at = (e.line() > 0).then(|| (e.line(), e.column()));
+                syntax(without_position(e.to_string(), at), at)
+            }),
+        }
+    }
+}
+
+/// A front matter problem, `line` and `column` are 1-based.
+#[derive(Debug, Clone)]
+pub struct FrontMatterError {
+    pub kind: FrontMatterErrorKind,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrontMatterErrorKind {
+    /// Not valid YAML, TOML or JSON, or a field of the wrong type
+    Syntax(FrontMatterFormat),
+    /// A `date` that `PostDate` cannot read
+    Date,
+}
+
+impl FrontMatterError {
+    /// Point at the `date` key in `text`.
+    pub fn date(message: String, text: &str) -> Self {
+        let at = text.lines().enumerate().find_map(|(i, line)| {
+            let key = line.trim_start();
+            let rest = key
+                .strip_prefix("\"date\"")
+                .or_else(|| key.strip_prefix("date"))?;
+            rest.trim_start()
+                .starts_with([':', '='])
+                .then(|| (i + 1, line.len() - key.len() + 1))
+        });
+        Self {
+            kind: FrontMatterErrorKind::Date,
+            line: at.map(|(line, _)| line),
+            column: at.map(|(_, column)| column),
+            message,
+        }
+    }
+
+    /// Make the position relative to the file rather than the front matter text.
+    pub fn shift_lines(mut self, lines: usize) -> Self {
+        self.line = self.line.map(|line| line + lines);
+        self
+    }
+}
+
+impl fmt::Display for FrontMatterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(line) = self.line {
+            write!(f, "line {line}")?;
+            if let Some(column) = self.column {
+                write!(f, ", column {column}")?;
+            }
+            write!(f, ": ")?;
+        }
+        match self.kind {
+            FrontMatterErrorKind::Syntax(format) => {
+                write!(f, "{}: {}", format.name(), self.message)
+            }
+            FrontMatterErrorKind::Date => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for FrontMatterError {}
+
+/// Split the JSON object opening `input` from the markdown after it.
+pub fn split_json(input: &str) -> Result<(&str, &str), FrontMatterError> {
+    let mut values = serde_json::Deserializer::from_str(input).into_iter::<IgnoredAny>();
+    match values.next() {
+        Some(Ok(_)) => Ok(input.split_at(values.byte_offset())),
+        Some(Err(e)) => Err(FrontMatterError {
+            kind: FrontMatterErrorKind::Syntax(FrontMatterFormat::Json),
+            line: Some(e.line()),
+            column: Some(e.column()),
+            message: without_position(e.to_string(), Some((e.line(), e.column()))),
+        }),
+        None => Ok(("", input)),
+    }
+}
+
+fn starts_with_json_object(input: &str) -> bool {
+    serde_json::Deserializer::from_str(input)
+        .into_iter::<BTreeMap<String, IgnoredAny>>()
+        .next()
+        .is_some_and(|object| object.is_ok())
+}
+
+/// serde errors repeat positions in their message, relative to the front matter text;
+/// we report the position separately and relative to the file, so drop them.
+fn without_position(message: String, at: Option<(usize, usize)>) -> String {
+    if at.is_none() {
+        return message;
+    }
+    let mut out = String::with_capacity(message.len());
+    let mut rest = message.as_str();
+    while let Some(pos) = rest.find(" at line ") {
+        out.push_str(&rest[..pos]);
+        let tail = &rest[pos + " at line ".len()..];
+        let digits = |s: &str| s.len() - s.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        let line_len = digits(tail);
+        match tail[line_len..].strip_prefix(" column ") {
+            Some(after) if line_len > 0 && digits(after) > 0 => rest = &after[digits(after)..],
+            _ => {
+                out.push_str(" at line ");
+                rest = tail;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// 1-based line and column of byte `offset`.
+fn line_column(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset.min(text.len())];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
+/// TOML dates become strings, everything else maps one to one.
+fn toml_to_json(value: toml::Value) -> serde_json::Value {
+    match value {
+        toml::Value::String(s) => s.into(),
+        toml::Value::Integer(i) => i.into(),
+        toml::Value::Float(f) => f.into(),
+        toml::Value::Boolean(b) => b.into(),
+        toml::Value::Datetime(dt) => dt.to_string().into(),
+        toml::Value::Array(items) => items.into_iter().map(toml_to_json).collect(),
+        toml::Value::Table(table) => table
+            .into_iter()
+            .map(|(key, value)| (key, toml_to_json(value)))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_by_first_line() {
+        assert_eq!(
+            FrontMatterFormat::detect("---\ntitle: a\n---\n"),
+            Some(FrontMatterFormat::Yaml)
+        );
+        assert_eq!(
+            FrontMatterFormat::detect("+++\r\ntitle = 'a'\r\n+++\r\n"),
+            Some(FrontMatterFormat::Toml)
+        );
+        assert_eq!(
+            FrontMatterFormat::detect("{\n\"title\": \"a\"\n}\n"),
+            Some(FrontMatterFormat::Json)
+        );
+        assert_eq!(
+            FrontMatterFormat::detect("{\"title\": \"a\"}\nbody"),
+            Some(FrontMatterFormat::Json)
+        );
+        assert_eq!(FrontMatterFormat::detect("# Title\n"), None);
+        assert_eq!(FrontMatterFormat::detect(""), None);
+    }
+
+    #[test]
+    fn braces_that_are_not_json_stay_markdown() {
+        assert_eq!(
+            FrontMatterFormat::detect("{{< figure src=\"a.png\" >}}\n"),
+            None
+        );
+        assert_eq!(
+            FrontMatterFormat::detect("{% raw %}\n{{ x }}\n{% endraw %}\n"),
+            None
+        );
+        assert_eq!(FrontMatterFormat::detect("{[1, 2]}\n"), None);
+    }
+
+    #[test]
+    fn a_lone_brace_is_json_even_when_broken() {
+        assert_eq!(
+            FrontMatterFormat::detect("{\n\"title\": \"a\",\n"),
+            Some(FrontMatterFormat::Json)
+        );
+    }
+
+    #[test]
+    fn splits_json_from_the_body() {
+        let (json, rest) = split_json("{\"title\": \"a {b}\"}\n# Body {c}\n").unwrap();
+        assert_eq!(json, "{\"title\": \"a {b}\"}");
+        assert_eq!(rest, "\n# Body {c}\n");
+    }
+
+    #[test]
+    fn split_json_reports_where_it_broke() {
+        let err = split_json("{\n\"title\": \"a\"\n\"date\": 1\n}\n").unwrap_err();
+        assert_eq!(
+            err.kind,
+            FrontMatterErrorKind::Syntax(FrontMatterFormat::Json)
+        );
+        assert_eq!((err.line, err.column), (Some(3), Some(1)));
+        assert!(!err.message.contains("at line"), "{}", err.message);
+    }
+
+    #[test]
+    fn strips_positions_from_messages() {
+        let message = "expected `,` at line 3 column 1".to_owned();
+        assert_eq!(without_position(message, Some((3, 1))), "expected `,`");
+        let message = "a: b at line 2 column 5, then c at line 4 column 1".to_owned();
+        assert_eq!(without_position(message, Some((2, 5))), "a: b, then c");
+    }
+
+    #[test]
+    fn keeps_messages_without_a_position() {
+        let message = "missing at line 3 column 1".to_owned();
+        assert_eq!(without_position(message.clone(), None), message);
+        let message = "stopped at line end".to_owned();
+        assert_eq!(without_position(message.clone(), Some((1, 1))), message);
+    }
+}
@@ -0,0 +1,278 @@
+use comrak::{adapters::SyntaxHighlighterAdapter, html::write_opening_tag};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt::{self, Write},
+    path::Path,
+    sync::{LazyLock, Mutex},
+};
+use syntect::{
+    easy::HighlightLines,
+    highlighting::{Color, ThemeSet},
+    html::{
+        ClassStyle, ClassedHTMLGenerator, IncludeBackground, css_for_theme_with_class_style,
+        styled_line_to_highlighted_html,
+    },
+    parsing::{SyntaxReference, SyntaxSet},
+    util::LinesWithEndings,
+};
+
+use crate::config::{HighlightMode, HighlightSection};
+
+pub const HIGHLIGHT_CSS_FILE: &str = "highlight.css";
+
+const CLASS_STYLE: ClassStyle = ClassStyle::SpacedPrefixed { prefix: "hl-" };
+
+static SYNTAX_SET: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
+static THEME_SET: LazyLock<ThemeSet> = LazyLock::new(ThemeSet::load_defaults);
+
+pub fn theme_exists(name: &str) -> bool {
+    THEME_SET.themes.contains_key(name)
+}
+
+/// Highlights fenced code with syntect while comrak renders the document.
+/// Create one per document: the fence meta travels from `write_code_tag` to `write_highlighted`.
+pub struct CodeHighlighter<'a> {
+    config: &'a HighlightSection,
+    /// The document, for warnings
+    path: &'a Path,
+    /// fence info after the language, e.g. `{3,5-7}`
+    meta: Mutex<Option<String>>,
+}
+
+impl<'a> CodeHighlighter<'a> {
+    pub fn new(config: &'a HighlightSection, path: &'a Path) -> Self {
+        Self {
+            config,
+            path,
+            meta: Mutex::new(None),
+        }
+    }
+
+    fn highlight(&self, syntax: &SyntaxReference, code: &str) -> Result<String, syntect::Error> {
+        match self.config.mode {
+            HighlightMode::Css => {
+                let mut generator =
+                    ClassedHTMLGenerator::new_with_class_style(syntax, &SYNTAX_SET, CLASS_STYLE);
+                for line in LinesWithEndings::from(code) {
+                    generator.parse_html_for_line_which_includes_newline(line)?;
+                }
+                Ok(generator.finalize())
+            }
+            HighlightMode::Inline => {
+                let theme = &THEME_SET.themes[&self.config.theme];
+                let mut highlighter = HighlightLines::new(syntax, theme);
+                let mut html = String::new();
+                for line in LinesWithEndings::from(code) {
+                    let regions = highlighter.highlight_line(line, &SYNTAX_SET)?;
+                    html.push_str(&styled_line_to_highlighted_html(
+                        &regions,
+                        IncludeBackground::No,
+                    )?);
+                }
+                Ok(html)
+            }
+        }
+    }
+}
+
+impl SyntaxHighlighterAdapter for CodeHighlighter<'_> {
+    fn write_highlighted(
+        &self,
+        output: &mut dyn Write,
+        lang: Option<&str>,
+        code: &str,
+    ) -> fmt::Result {
+        let meta = self.meta.lock().expect("fence meta poisoned").take();
+        let line_count = code.lines().count();
+        let (marked, out_of_range) = meta
+            .as_deref()
+            .map(|meta| parse_line_ranges(meta, line_count))
+            .unwrap_or_default();
+        if !out_of_range.is_empty() {
+            eprintln!(
+                "warning: {}: highlighted lines `{}` are past the end of a {line_count} line code block",
+                self.path.display(),
+                out_of_range.join(",")
+            );
+        }
+        let syntax = lang
+            .filter(|l| !l.is_empty())
+            .and_then(|l| SYNTAX_SET.find_syntax_by_token(l))
+            .unwrap_or_else(|| SYNTAX_SET.find_syntax_plain_text());
+        let html = match self.highlight(syntax, code) {
+            Ok(html) => html,
+            Err(_) => {
+                let mut escaped = String::new();
+                comrak::html::escape(&mut escaped, code)?;
+                escaped
+            }
+        };
+        output.write_str(&wrap_lines(&html, line_count, &marked))
+    }
+
+    fn write_pre_tag(
+        &self,
+        output: &mut dyn Write,
+        attributes: HashMap<&'static str, Cow<'_, str>>,
+    ) -> fmt::Result {
+        let mut attributes: BTreeMap<_, _> = attributes.into_iter().collect();
+        match self.config.mode {
+            HighlightMode::Css => {
+                attributes.insert("class", "highlight hl-code".into());
+            }
+            HighlightMode::Inline => {
+                let theme = &THEME_SET.themes[&self.config.theme];
+                let c = theme.settings.background.unwrap_or(Color::WHITE);
+                let style = format!("background-color:#{:02x}{:02x}{:02x};", c.r, c.g, c.b);
+                attributes.insert("style", style.into());
+            }
+        }
+        write_opening_tag(output, "pre", attributes)
+    }
+
+    fn write_code_tag(
+        &self,
+        output: &mut dyn Write,
+        mut attributes: HashMap<&'static str, Cow<'_, str>>,
+    ) -> fmt::Result {
+        *self.meta.lock().expect("fence meta poisoned") =
+            attributes.remove("data-meta").map(Cow::into_owned);
+        let attributes: BTreeMap<_, _> = attributes.into_iter().collect();
+        write_opening_tag(output, "code", attributes)
+    }
+}
+
+/// `{3,5-7}` -> {3, 5, 6, 7}. Anything that is not a number or a range is ignored.
+/// Lines are clamped to `1..=line_count`, the parts reaching outside it are returned too.
+fn parse_line_ranges(meta: &str, line_count: usize) -> (BTreeSet<usize>, Vec<&str>) {
+    let mut out_of_range = Vec::new();
+    let Some(start) = meta.find('{') else {
+        return (BTreeSet::new(), out_of_range);
+    };
+    let end = meta[start..].find('}').map_or(meta.len(), |e| start + e);
+    let mut lines = BTreeSet::new();
+    for part in meta[start + 1..end].split(',').map(str::trim) {
+        match part.split_once('-') {
+            Some((from, to)) => {
+                if let (Ok(from), Ok(to)) =
+                    (from.trim().parse::<usize>(), to.trim().parse::<usize>())
+                {
+                    if from == 0 || to > line_count {
+                        out_of_range.push(part);
+                    }
+                    lines.extend(from.max(1)..=to.min(line_count));
+                }
+            }
+            None => {
+                if let Ok(line) = part.parse() {
+                    if (1..=line_count).contains(&line) {
+                        lines.insert(line);
+                    } else {
+                        out_of_range.push(part);
+                    }
+                }
+            }
+        }
+    }
+    (lines, out_of_range)
+}
+
+/// Wrap every line of highlighted HTML in `<span class="code-line">`, adding `hl` for the
+/// 1-based lines in `marked`. Spans left open at a line end are closed and reopened on the
+/// next line so the wrappers nest properly.
+fn wrap_lines(html: &str, line_count: usize, marked: &BTreeSet<usize>) -> String {
+    let mut out = String::with_capacity(html.len() + 32 * line_count);
+    let mut open: Vec<&str> = Vec::new();
+    // closing tags emitted after the final newline belong to the last line
+    let mut lines: Vec<String> = html.split('\n').map(str::to_owned).collect();
+    while lines.len() > line_count.max(1) {
+        let tail = lines.pop().unwrap_or_default();
+        if let Some(last) = lines.last_mut() {
+            last.push_str(&tail);
+        }
+    }
+    for (i, line) in lines.iter().enumerate() {
+        if marked.contains(&(i + 1)) {
+            out.push_str(r#"<span class="code-line hl">"#);
+        } else {
+            out.push_str(r#"<span class="code-line">"#);
+        }
+        open.iter().for_each(|tag| out.push_str(tag));
+        let mut rest = line.as_str();
+        while let Some(pos) = rest.find('<') {
+            let tag_end = rest[pos..].find('>').map_or(rest.len(), |e| pos + e + 1);
+            let tag = &rest[pos..tag_end];
+            if tag.starts_with("</span") {
+                open.pop();
+            } else if tag.starts_with("<span") {
+                open.push(tag);
+            }
+            rest = &rest[tag_end..];
+        }
+        out.push_str(line);
+        open.iter().for_each(|_| out.push_str("</span>"));
+        out.push_str("</span>\n");
+    }
+    out
+}
+
+/// Stylesheet for [`HighlightMode::Css`]: the light theme, and the dark theme behind
+/// `prefers-color-scheme` when one is configured.
+pub fn highlight_css(config: &HighlightSection) -> Result<String, syntect::Error> {
+    let mut css = css_for_theme_with_class_style(&THEME_SET.themes[&config.theme], CLASS_STYLE)?;
+    if let Some(dark) = &config.dark_theme {
+        let dark_css = css_for_theme_with_class_style(&THEME_SET.themes[dark], CLASS_STYLE)?;
+        let _ = write!(
+            css,
+            "\n@media (prefers-color-scheme: dark) {{\n{dark_css}\n}}\n"
+        );
+    }
+    Ok(css)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_lines_and_ranges() {
+        let (lines, out_of_range) = parse_line_ranges("{1, 3-4,x,5-}", 6);
+        assert_eq!(lines, BTreeSet::from([1, 3, 4]));
+        assert!(out_of_range.is_empty());
+    }
+
+    #[test]
+    fn no_braces_marks_nothing() {
+        assert_eq!(
+            parse_line_ranges("title=\"a.rs\"", 3),
+            (BTreeSet::new(), vec![])
+        );
+    }
+
+    #[test]
+    fn clamps_ranges_to_the_block() {
+        let (lines, out_of_range) = parse_line_ranges("{0,2-18446744073709551615,9}", 3);
+        assert_eq!(lines, BTreeSet::from([2, 3]));
+        assert_eq!(out_of_range, ["0", "2-18446744073709551615", "9"]);
+    }
+
+    #[test]
+    fn wraps_every_line_and_marks_some() {
+        let html = wrap_lines("a\nb\n", 2, &BTreeSet::from([2]));
+        assert_eq!(
+            html,
+            "<span class=\"code-line\">a</span>\n<span class=\"code-line hl\">b</span>\n"
+        );
+    }
+
+    #[test]
+    fn reopens_spans_across_lines() {
+        let html = wrap_lines("<span class=\"c\">/*\n*/</span>\n", 2, &BTreeSet::new());
+        assert_eq!(
+            html,
+            "<span class=\"code-line\"><span class=\"c\">/*</span></span>\n\
+             <span class=\"code-line\"><span class=\"c\">*/</span></span>\n"
+        );
+    }
+}
@@ -0,0 +1,347 @@
+use anyhow::{Context, Result};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use image::{
+    DynamicImage, ImageDecoder, ImageReader, codecs::avif::AvifEncoder, imageops::FilterType,
+    metadata::Orientation,
+};
+use rayon::prelude::*;
+use sha2::{Digest, Sha256};
+use std::{fs, io::Cursor, path::Path};
+
+use crate::config::{ImageFormat, ImagesSection};
+use crate::feed::escape_xml;
+
+/// Longest side of the placeholder inlined into the page, the browser's upscaling does the rest of the blur.
+const PLACEHOLDER_SIZE: u32 = 16;
+
+/// rav1e speed from 1 (slowest, smallest) to 10. AVIF is by far the slowest part of a build,
+/// variants are only encoded once though, their names carry the source hash.
+const AVIF_SPEED: u8 = 8;
+
+/// The resized copies of one image.
+#[derive(Debug, Clone)]
+pub struct Picture {
+    /// Of the widest variant, so the page reserves the right box before it loads
+    pub width: u32,
+    pub height: u32,
+    pub variants: Vec<Variant>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Variant {
+    pub format: ImageFormat,
+    pub width: u32,
+    /// File name under `assets/`
+    pub name: String,
+}
+
+/// PNG, JPEG and WebP, anything else (SVG, GIF) is copied as it is.
+pub fn is_resizable(path: &Path) -> bool {
+    matches!(
+        image::ImageFormat::from_path(path),
+        Ok(image::ImageFormat::Png | image::ImageFormat::Jpeg | image::ImageFormat::WebP)
+    )
+}
+
+/// The variants `source` gets, worked out from its header alone.
+/// `stem` and `hash` are those of the plain copy, so names change with the content and the settings.
+pub fn plan(source: &Path, stem: &str, hash: &str, settings: &ImagesSection) -> Result<Picture> {
+    let mut decoder = open(source)?;
+    let orientation = decoder.orientation()?;
+    let (mut width, mut height) = decoder.dimensions();
+    if matches!(
+        orientation,
+        Orientation::Rotate90
+            | Orientation::Rotate270
+            | Orientation::Rotate90FlipH
+            | Orientation::Rotate270FlipH
+    ) {
+        (width, height) = (height, width);
+    }
+    // 不放大, 比原图宽的都按原图宽度出
+    let mut widths: Vec<u32> = settings.widths.iter().map(|&w| w.min(width)).collect();
+    widths.sort_unstable();
+    widths.dedup();
+    let widest = widths.last().copied().unwrap_or(width);
+    let variants = settings
+        .formats
+        .iter()
+        .flat_map(|&format| {
+            widths.iter().map(move |&w| {
+                let key = format!("{hash}:{w}:{}:{}", format.extension(), settings.quality);
+                let key = hex::encode(Sha256::digest(key));
+                Variant {
+                    format,
+                    width: w,
+                    name: format!("{stem}-{w}.{}.{}", &key[..10], format.extension()),
+                }
+            })
+        })
+        .collect();
+    Ok(Picture {
+        width: widest,
+        height: scaled_height(width, height, widest),
+        variants,
+    })
+}
+
+/// Write the variants missing from `dir` and return the placeholder, decoding `source` once for both.
+pub fn generate(
+    source: &Path,
+    picture: &Picture,
+    dir: &Path,
+    settings: &ImagesSection,
+) -> Result<Option<String>> {
+    let image = decode(source)?;
+    picture
+        .variants
+        .par_iter()
+        .filter(|variant| !dir.join(&variant.name).is_file())
+        .try_for_each(|variant| {
+            let resized = if variant.width >= image.width() {
+                image.clone()
+            } else {
+                let height = scaled_height(image.width(), image.height(), variant.width);
+                image.resize_exact(variant.width, height, FilterType::Lanczos3)
+            };
+            let bytes = encode(&resized, variant.format, settings.quality)
+                .with_context(|| format!("failed to encode {}", variant.name))?;
+            let dest = dir.join(&variant.name);
+            fs::write(&dest, bytes).with_context(|| format!("write to {} failed", dest.display()))
+        })?;
+    Ok(placeholder(&image))
+}
+
+/// Full decode with the EXIF orientation applied.
+pub fn decode(source: &Path) -> Result<DynamicImage> {
+    let mut decoder = open(source)?;
+    let orientation = decoder.orientation()?;
+    let mut image = DynamicImage::from_decoder(decoder)
+        .with_context(|| format!("failed to decode {}", source.display()))?;
+    image.apply_orientation(orientation);
+    Ok(image)
+}
+
+/// A tiny blurred copy as a `data:` URI. `None` for images with see-through pixels,
+/// the placeholder would show through them once the image is in.
+pub fn placeholder(image: &DynamicImage) -> Option<String> {
+    if image.color().has_alpha() && image.to_rgba8().pixels().any(|p| p[3] < u8::MAX) {
+        return None;
+    }
+    let small = image
+        .thumbnail(PLACEHOLDER_SIZE, PLACEHOLDER_SIZE)
+        .blur(1.0)
+        .to_rgb8();
+    let mut png = Cursor::new(Vec::new());
+    small.write_to(&mut png, image::ImageFormat::Png).ok()?;
+    Some(format!(
+        "data:image/png;base64,{}",
+        STANDARD.encode(png.into_inner())
+    ))
+}
+
+/// `<picture>` offering each variant of an image in every format, `alt` and `title` already escaped.
+/// `url` turns a site relative path into the address written in the page.
+pub fn picture_html(
+    src: &str,
+    picture: &Picture,
+    placeholder: Option<&str>,
+    alt: &str,
+    title: Option<&str>,
+    url: impl Fn(&str) -> String,
+) -> String {
+    let style = placeholder
+        .map(|data| format!(" style=\"background-image:url({data})\""))
+        .unwrap_or_default();
+    let sizes = format!(
+        "(max-width: {width}px) 100vw, {width}px",
+        width = picture.width
+    );
+    let mut html = format!("<picture class=\"sitegen-picture\"{style}>");
+    let mut formats: Vec<ImageFormat> = Vec::new();
+    for variant in &picture.variants {
+        if !formats.contains(&variant.format) {
+            formats.push(variant.format);
+        }
+    }
+    for format in formats {
+        let srcset = picture
+            .variants
+            .iter()
+            .filter(|variant| variant.format == format)
+            .map(|variant| {
+                let path = format!("{}/{}", crate::assets::ASSET_DIR, variant.name);
+                format!("{} {}w", escape_xml(&url(&path)), variant.width)
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        html.push_str(&format!(
+            "<source type=\"{}\" srcset=\"{srcset}\" sizes=\"{sizes}\" />",
+            format.mime_type()
+        ));
+    }
+    html.push_str(&format!(
+        "<img src=\"{}\" alt=\"{alt}\"{} width=\"{}\" height=\"{}\" loading=\"lazy\" decoding=\"async\" /></picture>",
+        escape_xml(src),
+        title
+            .map(|title| format!(" title=\"{title}\""))
+            .unwrap_or_default(),
+        picture.width,
+        picture.height,
+    ));
+    html
+}
+
+/// `<img>` for images that get no variants, lazy loaded like the `<picture>` ones.
+pub fn img_html(src: &str, alt: &str, title: Option<&str>) -> String {
+    format!(
+        "<img src=\"{}\" alt=\"{alt}\"{} loading=\"lazy\" decoding=\"async\" />",
+        escape_xml(src),
+        title
+            .map(|title| format!(" title=\"{title}\""))
+            .unwrap_or_default()
+    )
+}
+
+/// Rewrite every `<img>` whose `src` is `old`, together with the `<picture>` an earlier build
+/// wrapped it in. `markup` gets the tag's escaped `alt` and `title`.
+pub fn replace_img(html: &str, old: &str, markup: impl Fn(&str, Option<&str>) -> String) -> String {
+    const PICTURE_OPEN: &str = "<picture class=\"sitegen-picture\"";
+    const PICTURE_CLOSE: &str = "</picture>";
+    let needle = format!(" src=\"{}\"", escape_xml(old));
+    let mut out = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(at) = rest.find(&needle) {
+        let after = at + needle.len();
+        let Some(tag_start) = rest[..at].rfind("<img") else {
+            out.push_str(&rest[..after]);
+            rest = &rest[after..];
+            continue;
+        };
+        let tag_end = rest[after..]
+            .find('>')
+            .map_or(rest.len(), |i| after + i + 1);
+        let tag = &rest[tag_start..tag_end];
+        let (mut start, mut end) = (tag_start, tag_end);
+        // 之前的构建已经包了一层 <picture>
+        if let Some(open) = rest[..tag_start].rfind(PICTURE_OPEN)
+            && !rest[open..tag_start].contains(PICTURE_CLOSE)
+            && rest[tag_end..].starts_with(PICTURE_CLOSE)
+        {
+            (start, end) = (open, tag_end + PICTURE_CLOSE.len());
+        }
+        out.push_str(&rest[..start]);
+        out.push_str(&markup(
+            attribute(tag, "alt").unwrap_or_default(),
+            attribute(tag, "title"),
+        ));
+        rest = &rest[end..];
+    }
+    out.push_str(rest);
+    out
+}
+
+fn attribute<'t>(tag: &'t str, name: &str) -> Option<&'t str> {
+    let start = tag.find(&format!(" {name}=\""))? + name.len() + 3;
+    let len = tag[start..].find('"')?;
+    Some(&tag[start..start + len])
+}
+
+fn open(source: &Path) -> Result<impl ImageDecoder> {
+    ImageReader::open(source)
+        .with_context(|| format!("failed to read: {}", source.display()))?
+        .with_guessed_format()
+        .with_context(|| format!("failed to read: {}", source.display()))?
+        .into_decoder()
+        .with_context(|| format!("failed to decode {}", source.display()))
+}
+
+fn scaled_height(width: u32, height: u32, to_width: u32) -> u32 {
+    ((u64::from(height) * u64::from(to_width) / u64::from(width.max(1))) as u32).max(1)
+}
+
+fn encode(image: &DynamicImage, format: ImageFormat, quality: u8) -> Result<Vec<u8>> {
+    match format {
+        ImageFormat::Avif => {
+            let mut out = Vec::new();
+            let encoder = AvifEncoder::new_with_speed_quality(&mut out, AVIF_SPEED, quality);
+            if image.color().has_alpha() {
+                DynamicImage::ImageRgba8(image.to_rgba8()).write_with_encoder(encoder)?;
+            } else {
+                DynamicImage::ImageRgb8(image.to_rgb8()).write_with_encoder(encoder)?;
+            }
+            Ok(out)
+        }
+        ImageFormat::Webp => {
+            let rgba = image.to_rgba8();
+            let encoded = webp::Encoder::from_rgba(&rgba, rgba.width(), rgba.height())
+                .encode_simple(false, f32::from(quality))
+                .map_err(|e| anyhow::anyhow!("{e:?}"))?;
+            Ok(encoded.to_vec())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn picture() -> Picture {
+        Picture {
+            width: 480,
+            height: 240,
+            variants: vec![Variant {
+                format: ImageFormat::Webp,
+                width: 480,
+                name: "a-480.0123456789.webp".to_owned(),
+            }],
+        }
+    }
+
+    fn wrap(alt: &str, title: Option<&str>) -> String {
+        picture_html("a.png", &picture(), None, alt, title, |path| {
+            format!("/{path}")
+        })
+    }
+
+    #[test]
+    fn wraps_a_plain_img() {
+        let html = "<p><img src=\"a.png\" alt=\"A &amp; B\" title=\"T\" /></p>";
+        let out = replace_img(html, "a.png", wrap);
+        assert_eq!(out, format!("<p>{}</p>", wrap("A &amp; B", Some("T"))));
+        assert!(out.contains("srcset=\"/assets/a-480.0123456789.webp 480w\""));
+    }
+
+    #[test]
+    fn rewraps_a_picture_from_an_earlier_build() {
+        let html = format!("<p>{}</p>", wrap("old", None));
+        let out = replace_img(&html, "a.png", wrap);
+        assert_eq!(out, format!("<p>{}</p>", wrap("old", None)));
+        assert_eq!(out.matches("<picture").count(), 1);
+        let out = replace_img(&out, "a.png", |alt, title| img_html("b.png", alt, title));
+        assert_eq!(
+            out,
+            "<p><img src=\"b.png\" alt=\"old\" loading=\"lazy\" decoding=\"async\" /></p>"
+        );
+    }
+
+    #[test]
+    fn leaves_other_images_and_pictures_alone() {
+        let other = picture_html("c.png", &picture(), None, "c", None, |path| path.to_owned());
+        let html = format!("{other}<img src=\"a.png\" alt=\"a\" /><img src=\"ab.png\" alt=\"\" />");
+        let out = replace_img(&html, "a.png", |alt, title| img_html("x.png", alt, title));
+        assert_eq!(
+            out,
+            format!(
+                "{other}<img src=\"x.png\" alt=\"a\" loading=\"lazy\" decoding=\"async\" />\
+                 <img src=\"ab.png\" alt=\"\" />"
+            )
+        );
+    }
+
+    #[test]
+    fn ignores_src_outside_an_img() {
+        let html = "<source src=\"a.png\" /><p>text src=\"a.png\"</p>";
+        assert_eq!(replace_img(html, "a.png", |_, _| "X".to_owned()), html);
+    }
+}
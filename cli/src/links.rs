@@ -0,0 +1,155 @@
+use comrak::nodes::{AstNode, NodeValue};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+/// Stands in for a link's `href` until the target's permalink is known.
+const PLACEHOLDER_SCHEME: &str = "sitegen-link:";
+
+/// A link from one post to another markdown file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PostLink {
+    /// As written, e.g. `../rust/async.md#pinning`
+    pub written: String,
+    /// The markdown file it points at, relative to the linking post
+    pub target: PathBuf,
+    pub fragment: Option<String>,
+    pub line: usize,
+    /// What `content` holds for this link right now
+    pub href: String,
+}
+
+/// The permalink and heading anchors a link can point at.
+pub struct LinkTarget {
+    pub url: String,
+    pub anchors: Vec<String>,
+}
+
+impl PostLink {
+    /// The published post `targets` has for this link, or why there is none.
+    /// `targets` is keyed by canonical source path.
+    pub fn resolve<'t>(
+        &self,
+        targets: &'t HashMap<PathBuf, LinkTarget>,
+    ) -> Result<&'t LinkTarget, String> {
+        let Some(target) = fs::canonicalize(&self.target)
+            .ok()
+            .and_then(|target| targets.get(&target))
+        else {
+            return Err(if self.target.exists() {
+                "points at a post that is not published".to_owned()
+            } else {
+                "points at a file that does not exist".to_owned()
+            });
+        };
+        if let Some(fragment) = &self.fragment
+            && !target.anchors.contains(fragment)
+        {
+            return Err(format!(
+                "points at a heading not found in {}",
+                self.target.display()
+            ));
+        }
+        Ok(target)
+    }
+}
+
+/// Swap every relative `.md` link under `root` for a placeholder and return them in document order.
+pub fn collect_post_links<'a>(root: &'a AstNode<'a>, path: &Path) -> Vec<PostLink> {
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let mut links = Vec::new();
+    for node in root.descendants() {
+        let mut ast = node.data.borrow_mut();
+        let line = ast.sourcepos.start.line;
+        let NodeValue::Link(ref mut link) = ast.value else {
+            continue;
+        };
+        let (file, fragment) = match link.url.split_once('#') {
+            Some((file, fragment)) => (file, Some(fragment)),
+            None => (link.url.as_str(), None),
+        };
+        if !is_relative_markdown(file) {
+            continue;
+        }
+        let href = format!("{PLACEHOLDER_SCHEME}{}", links.len());
+        links.push(PostLink {
+            written: link.url.clone(),
+            target: join_relative(dir, &decode_path(file)),
+            fragment: fragment.filter(|f| !f.is_empty()).map(str::to_owned),
+            line,
+            href: href.clone(),
+        });
+        link.url = href;
+    }
+    links
+}
+
+/// `None` for malformed escapes or bytes that are not UTF-8.
+pub fn percent_decode(path: &str) -> Option<String> {
+    let bytes = path.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            out.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+/// A file path as written in a link, decoded; a stray `%` is taken literally.
+pub fn decode_path(file: &str) -> String {
+    percent_decode(file).unwrap_or_else(|| file.to_owned())
+}
+
+/// `dir.join(rel)` with `.` and `..` folded away, so reports show tidy paths.
+pub fn join_relative(dir: &Path, rel: &str) -> PathBuf {
+    let mut path = dir.to_path_buf();
+    for component in Path::new(rel).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir
+                if matches!(path.components().next_back(), Some(Component::Normal(_))) =>
+            {
+                path.pop();
+            }
+            other => path.push(other),
+        }
+    }
+    path
+}
+
+/// A path relative to the post, not `http:`, `mailto:` and the like or a site absolute path.
+pub fn is_local(url: &str) -> bool {
+    let has_scheme = url
+        .split_once(':')
+        .is_some_and(|(scheme, _)| !scheme.contains('/'));
+    !has_scheme && !url.is_empty() && !url.starts_with(['/', '#', '?'])
+}
+
+fn is_relative_markdown(url: &str) -> bool {
+    let lower = url.to_ascii_lowercase();
+    is_local(url) && (lower.ends_with(".md") || lower.ends_with(".markdown"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_escapes_in_link_paths() {
+        assert_eq!(decode_path("%E4%BD%A0%20%E5%A5%BD.md"), "你 好.md");
+        assert_eq!(decode_path("c%23%2fnotes.md"), "c#/notes.md");
+        assert_eq!(decode_path("100%.md"), "100%.md");
+        assert_eq!(percent_decode("%zz"), None);
+        assert_eq!(percent_decode("%ff"), None);
+    }
+}
@@ -1,24 +1,30 @@
 use anyhow::Result;
-use std::{env, path::PathBuf};
+use clap::Parser;
 
-use crate::markdown_meta::Index;
+use crate::commands::Cli;
 
+mod assets;
+mod commands;
+mod config;
+mod excerpt;
+mod feed;
+mod front_matter;
+mod highlight;
+mod images;
+mod links;
+mod manifest;
 mod markdown_meta;
+mod math;
+mod outline;
+mod permalink;
+mod prerender;
+mod schedule;
+mod search;
+mod sitemap;
+mod stats;
+mod watch;
 
-fn help() {
-    println!("Usage: convert markdown to json in specified paths.")
-}
 fn main() -> Result<()> {
-    let args: Vec<String> = env::args().collect();
-    match args.len() {
-        1 => {
-            help();
-            Ok(())
-        }
-        _ => {
-            let paths: Vec<PathBuf> = args.iter().skip(1).map(PathBuf::from).collect();
-            let index: Index = paths.try_into()?;
-            Ok(())
-        }
-    }
+    let cli = Cli::parse();
+    commands::run(cli)
 }
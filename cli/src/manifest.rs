@@ -0,0 +1,237 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+pub const MANIFEST_FILE: &str = ".sitegen-manifest.json";
+
+/// What the previous build produced, persisted in the output dir so unchanged posts can be skipped.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct BuildManifest {
+    /// source markdown path -> entry
+    entries: BTreeMap<PathBuf, ManifestEntry>,
+    /// Prerendered HTML pages, they are all rewritten on every build
+    #[serde(default)]
+    pages: BTreeSet<PathBuf>,
+    /// Files copied to `assets/`
+    #[serde(default)]
+    assets: BTreeSet<PathBuf>,
+    /// The output dir the manifest was loaded from, nothing outside it is ever deleted
+    #[serde(skip)]
+    root: PathBuf,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// sha256 over the build fingerprint and the markdown source
+    pub hash: String,
+    pub output: PathBuf,
+}
+
+impl BuildManifest {
+    /// A missing or unreadable manifest just means everything gets rebuilt.
+    pub fn load(dist_dir: &Path) -> Self {
+        let path = dist_dir.join(MANIFEST_FILE);
+        let mut manifest: Self = fs::read_to_string(&path)
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+        manifest.root = dist_dir.to_path_buf();
+        manifest
+    }
+
+    pub fn save(&self, dist_dir: &Path) -> Result<()> {
+        let path = dist_dir.join(MANIFEST_FILE);
+        let json = serde_json::to_string_pretty(self).context("serialize manifest failed")?;
+        fs::write(&path, json).with_context(|| format!("write to {} failed", path.display()))
+    }
+
+    /// The recorded output for `source`, if it was built from exactly `hash` and still exists.
+    pub fn fresh_output(&self, source: &Path, hash: &str) -> Option<&Path> {
+        self.entries
+            .get(source)
+            .filter(|e| e.hash == hash && e.output.is_file())
+            .map(|e| e.output.as_path())
+    }
+
+    pub fn record(&mut self, source: PathBuf, hash: String, output: PathBuf) {
+        self.entries.insert(source, ManifestEntry { hash, output });
+    }
+
+    pub fn forget(&mut self, source: &Path) {
+        self.entries.remove(source);
+    }
+
+    pub fn forget_all<P: AsRef<Path>>(&mut self, sources: impl IntoIterator<Item = P>) {
+        for source in sources {
+            self.forget(source.as_ref());
+        }
+    }
+
+    /// Every output currently recorded.
+    pub fn outputs(&self) -> BTreeSet<PathBuf> {
+        self.entries.values().map(|e| e.output.clone()).collect()
+    }
+
+    /// Delete the outputs in `before` no entry points at any more, e.g. after a permalink changed.
+    pub fn remove_moved_outputs(&self, before: &BTreeSet<PathBuf>) {
+        for moved in before.difference(&self.outputs()) {
+            remove_output(&self.root, moved);
+        }
+    }
+
+    /// Drop entries whose source file is gone and delete their outputs.
+    /// Returns how many were removed.
+    pub fn prune_missing_sources(&mut self) -> usize {
+        let gone: Vec<PathBuf> = self
+            .entries
+            .keys()
+            .filter(|source| !source.exists())
+            .cloned()
+            .collect();
+        for source in &gone {
+            let Some(entry) = self.entries.remove(source) else {
+                continue;
+            };
+            remove_output(&self.root, &entry.output);
+        }
+        gone.len()
+    }
+
+    /// Remember the pages this build wrote and delete the ones it no longer writes.
+    pub fn replace_pages(&mut self, pages: BTreeSet<PathBuf>) {
+        for stale in self.pages.difference(&pages) {
+            remove_output(&self.root, stale);
+        }
+        self.pages = pages;
+    }
+
+    /// Same as `replace_pages`, for the copied assets.
+    pub fn replace_assets(&mut self, assets: BTreeSet<PathBuf>) {
+        for stale in self.assets.difference(&assets) {
+            remove_output(&self.root, stale);
+        }
+        self.assets = assets;
+    }
+}
+
+/// Delete a file this tool wrote, then the directories it leaves empty up to `root`.
+/// Paths outside `root`, e.g. from a hand edited manifest, are left alone.
+fn remove_output(root: &Path, path: &Path) {
+    let inside = path.starts_with(root)
+        && path != root
+        && !path.components().any(|c| c == Component::ParentDir);
+    if !inside {
+        eprintln!(
+            "warning: not removing {}, it is outside {}",
+            path.display(),
+            root.display()
+        );
+        return;
+    }
+    if let Err(e) = fs::remove_file(path)
+        && e.kind() != std::io::ErrorKind::NotFound
+    {
+        eprintln!("Failed to remove {}: {e}", path.display());
+    }
+    // remove_dir only succeeds on empty directories, so this stops at the first one still in use
+    for dir in path.ancestors().skip(1) {
+        if dir == root || fs::remove_dir(dir).is_err() {
+            break;
+        }
+    }
+}
+
+/// Hash of a markdown source together with everything else that affects its output.
+pub fn source_hash(input: &[u8], fingerprint: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(fingerprint.as_bytes());
+    hasher.update([0u8]);
+    hasher.update(input);
+    hex::encode(hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh `<tmp>/<name>/dist` holding `files`, returned with its parent.
+    fn site(name: &str, files: &[&str]) -> (PathBuf, PathBuf) {
+        let base =
+            std::env::temp_dir().join(format!("sitegen-manifest-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&base);
+        let root = base.join("dist");
+        fs::create_dir_all(&root).unwrap();
+        for file in files {
+            let path = base.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "x").unwrap();
+        }
+        (base, root)
+    }
+
+    #[test]
+    fn never_removes_outside_root() {
+        let (base, root) = site("outside", &["keep.txt", "other/keep.txt"]);
+        remove_output(&root, &base.join("keep.txt"));
+        remove_output(&root, &root.join("../other/keep.txt"));
+        remove_output(&root, &root);
+        assert!(base.join("keep.txt").is_file());
+        assert!(base.join("other/keep.txt").is_file());
+        assert!(root.is_dir());
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn removes_empty_parents_up_to_root() {
+        let (base, root) = site("parents", &["dist/2024/01/a.json", "dist/2024/02/b.json"]);
+        remove_output(&root, &root.join("2024/01/a.json"));
+        assert!(!root.join("2024/01").exists());
+        assert!(root.join("2024/02/b.json").is_file());
+        remove_output(&root, &root.join("2024/02/b.json"));
+        assert!(!root.join("2024").exists());
+        assert!(root.is_dir());
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn prunes_outputs_of_deleted_sources() {
+        let (base, root) = site(
+            "prune",
+            &["notes/kept.md", "dist/kept.json", "dist/tags/gone.json"],
+        );
+        let mut manifest = BuildManifest::load(&root);
+        manifest.record(
+            base.join("notes/kept.md"),
+            "1".into(),
+            root.join("kept.json"),
+        );
+        manifest.record(
+            base.join("notes/gone.md"),
+            "2".into(),
+            root.join("tags/gone.json"),
+        );
+        assert_eq!(manifest.prune_missing_sources(), 1);
+        assert!(root.join("kept.json").is_file());
+        assert!(!root.join("tags").exists());
+        assert_eq!(manifest.outputs(), BTreeSet::from([root.join("kept.json")]));
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn removes_outputs_that_moved() {
+        let (base, root) = site("moved", &["dist/old.json", "dist/new.json"]);
+        let mut manifest = BuildManifest::load(&root);
+        manifest.record(base.join("a.md"), "1".into(), root.join("old.json"));
+        let before = manifest.outputs();
+        manifest.record(base.join("a.md"), "2".into(), root.join("new.json"));
+        manifest.remove_moved_outputs(&before);
+        assert!(!root.join("old.json").exists());
+        assert!(root.join("new.json").is_file());
+        fs::remove_dir_all(&base).unwrap();
+    }
+}
@@ -1,45 +1,179 @@
 use anyhow::{Context, Result};
-use chrono::NaiveDate;
-use comrak::{Arena, Options, nodes::NodeValue};
+use chrono::{DateTime, FixedOffset, NaiveDate, Utc};
+use comrak::{
+    Arena,
+    nodes::{AstNode, NodeValue},
+    options::Plugins,
+};
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     fs,
     path::{Path, PathBuf},
     time::UNIX_EPOCH,
 };
 
+use crate::assets::{AssetStore, PostAsset, collect_post_assets};
+use crate::config::{HighlightMode, SiteConfig, SiteSection};
+use crate::excerpt::Excerpt;
+use crate::feed::{
+    ATOM_FILE, Feed, FeedEntry, JSON_FEED_FILE, RSS_FILE, TAG_DIR, TagDirs, absolute_url,
+    escape_xml, has_absolute_base_url, tag_dir,
+};
+use crate::front_matter::{FrontMatterError, FrontMatterFormat, split_json};
+use crate::highlight::{CodeHighlighter, HIGHLIGHT_CSS_FILE, highlight_css};
+use crate::images;
+use crate::links::{LinkTarget, PostLink, collect_post_links};
+use crate::manifest::{BuildManifest, source_hash};
+use crate::math::render_math;
+use crate::outline::{HeadingAnchors, OutlineItem};
+use crate::permalink::{self, slugify};
+use crate::prerender::{ListedPost, PageShell, PostPage, write_page};
+use crate::schedule::PostDate;
+use crate::search::{SearchIndex, SearchInput};
+use crate::sitemap::{ARCHIVE_URL, SitemapEntry, percent_encode_path, write_robots, write_sitemap};
+use crate::stats::{TextStats, plain_text};
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FrontMatter {
     pub title: String,
+    /// Names the post in its permalink, from the title when not given
+    pub slug: String,
     pub author: String,
     pub tags: Vec<String>,
-    pub date: NaiveDate,
+    /// Posts dated after the build time are held back unless `--future` is given
+    pub date: PostDate,
+    /// Overrides the excerpt taken from the body
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+    /// Left out of the build unless `--drafts` is given
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub draft: bool,
+    /// Built and reachable by URL, but kept out of the toc, topics, feeds, sitemap and search
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub unlisted: bool,
+    /// Keys the front matter has beyond the ones above, passed through to the web app
+    #[serde(default, skip_serializing_if = "serde_json::Value::is_null")]
+    pub extra: serde_json::Value,
 }
-#[derive(Debug, Serialize)]
+
+/// Front matter as written: everything but `date` may be left out, unknown keys are kept.
+#[derive(Debug, Default, Deserialize)]
+struct RawFrontMatter {
+    title: Option<String>,
+    slug: Option<String>,
+    author: Option<String>,
+    tags: Option<Vec<String>>,
+    /// Parsed in `resolve`, so a bad date gets its own error
+    date: Option<String>,
+    summary: Option<String>,
+    #[serde(default)]
+    draft: bool,
+    #[serde(default)]
+    unlisted: bool,
+    #[serde(flatten)]
+    extra: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Markdown {
     // file meta info
     path: PathBuf,
     modified_at_unix: Option<u64>,
     metadata: FrontMatter,
+    /// Headings in document order, `anchor` is the `id` they carry in `content`
+    outline: Vec<OutlineItem>,
+    stats: TextStats,
+    excerpt: Excerpt,
+    /// Links to other posts, their `href` is filled in once every permalink is known
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    links: Vec<PostLink>,
+    /// Local images and files, their `href` is filled in once they are copied
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    assets: Vec<PostAsset>,
     // content, think when dumping json, content should be a HTML string
     content: String,
 }
 
 #[derive(Debug, Serialize)]
 pub struct Index {
-    paragraph_under_certain_topic: HashMap<String, Vec<String>>,
+    site: SiteSection,
+    /// Tag to the `path` of every listed post carrying it
+    paragraph_under_certain_topic: BTreeMap<String, Vec<String>>,
     table_of_content: Vec<TableOfContentItem>,
+    /// Whether posts have a page at their `url`, the web app links them as `?post=<path>` otherwise
+    prerendered: bool,
     #[serde(skip_serializing)]
     markdowns: Vec<Markdown>,
+    /// Unlisted posts, they only get their own page
+    #[serde(skip_serializing)]
+    unlisted: Vec<TableOfContentItem>,
+    #[serde(skip_serializing)]
+    summary: BuildSummary,
+}
+
+/// Per-invocation switches that are not part of the site config.
+#[derive(Debug, Default, Clone)]
+pub struct BuildOptions {
+    /// Ignore the build manifest and convert every post again
+    pub force: bool,
+    /// Publish posts marked `draft: true`
+    pub drafts: bool,
+    /// Publish posts dated after the build time
+    pub future: bool,
+}
+
+impl BuildOptions {
+    /// Whether a build at `now` publishes the post, `timezone` is `build.timezone`.
+    pub fn publishes(
+        &self,
+        metadata: &FrontMatter,
+        timezone: Option<FixedOffset>,
+        now: DateTime<Utc>,
+    ) -> bool {
+        (self.drafts || !metadata.draft)
+            && (self.future || metadata.date.publish_at(timezone) <= now)
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct BuildSummary {
+    pub converted: usize,
+    pub unchanged: usize,
+    pub removed: usize,
+    /// Drafts left out because `--drafts` was not given
+    pub skipped_drafts: Vec<PathBuf>,
+    /// Posts dated after the build time, left out because `--future` was not given
+    pub scheduled: Vec<PathBuf>,
+    pub unlisted: Vec<PathBuf>,
+    /// Files under the content roots that no post links to or embeds
+    pub unreferenced_assets: Vec<PathBuf>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct TableOfContentItem {
     title: String,
     path: String,
+    /// Site relative address of the post page
+    url: String,
     date: NaiveDate,
+    tags: Vec<String>,
+    stats: TextStats,
+    excerpt: Excerpt,
 }
+impl TableOfContentItem {
+    fn listed(&self) -> ListedPost<'_> {
+        ListedPost {
+            title: &self.title,
+            url: &self.url,
+            date: self.date,
+            stats: self.stats,
+            excerpt: &self.excerpt.text,
+        }
+    }
+}
+
 pub fn is_markdown(path: &Path) -> bool {
     path.extension()
         .and_then(|s| s.to_str())
@@ -49,6 +183,135 @@ pub fn is_markdown(path: &Path) -> bool {
 impl TryFrom<PathBuf> for Markdown {
     type Error = anyhow::Error;
     fn try_from(path: PathBuf) -> Result<Self> {
+        Self::from_path(path, &SiteConfig::default())
+    }
+}
+
+impl Markdown {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn metadata(&self) -> &FrontMatter {
+        &self.metadata
+    }
+
+    pub fn links(&self) -> &[PostLink] {
+        &self.links
+    }
+
+    pub fn assets(&self) -> &[PostAsset] {
+        &self.assets
+    }
+
+    /// What links to this post at `url` can point at.
+    pub fn link_target(&self, url: String) -> LinkTarget {
+        LinkTarget {
+            url,
+            anchors: self
+                .outline
+                .iter()
+                .map(|item| item.anchor.clone())
+                .collect(),
+        }
+    }
+
+    /// Point links to other posts at their permalinks, true when `content` changed.
+    /// Links whose file or heading does not exist are added to `broken`.
+    fn resolve_links(
+        &mut self,
+        targets: &HashMap<PathBuf, LinkTarget>,
+        site: &SiteSection,
+        broken: &mut Vec<String>,
+    ) -> bool {
+        let mut changed = false;
+        for link in &mut self.links {
+            let target = match link.resolve(targets) {
+                Ok(target) => target,
+                Err(reason) => {
+                    broken.push(format!(
+                        "{}:{}: `{}` {reason}",
+                        self.path.display(),
+                        link.line,
+                        link.written
+                    ));
+                    continue;
+                }
+            };
+            let mut href = absolute_url(site, &target.url);
+            if let Some(fragment) = &link.fragment {
+                href = format!("{href}#{fragment}");
+            }
+            if href != link.href {
+                replace_url(&mut self.content, &mut self.excerpt, &link.href, &href);
+                link.href = href;
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Copy local images and files into the output dir and point at the copies, images become
+    /// a `<picture>` of their resized variants. True when `content` changed.
+    /// Missing files are warned about and left as written.
+    fn resolve_assets(&mut self, store: &mut AssetStore, site: &SiteSection) -> Result<bool> {
+        let mut changed = false;
+        for asset in &mut self.assets {
+            let published = store.publish(&asset.source)?;
+            let href = match &published {
+                Some(published) => {
+                    format!("{}{}", absolute_url(site, &published.url), asset.suffix)
+                }
+                None => {
+                    eprintln!(
+                        "warning: {}:{}: missing file `{}`",
+                        self.path.display(),
+                        asset.line,
+                        asset.written
+                    );
+                    asset.written.clone()
+                }
+            };
+            if href == asset.href {
+                continue;
+            }
+            // 图片整个换成 <picture>, 之前包过的也一起换掉
+            match published
+                .and_then(|published| published.picture)
+                .filter(|_| asset.image)
+            {
+                Some(picture) => {
+                    let placeholder = store.placeholder(&asset.source)?;
+                    let markup = |alt: &str, title: Option<&str>| {
+                        images::picture_html(
+                            &href,
+                            &picture,
+                            placeholder.as_deref(),
+                            alt,
+                            title,
+                            |path| absolute_url(site, path),
+                        )
+                    };
+                    self.content = images::replace_img(&self.content, &asset.href, markup);
+                    self.excerpt.html =
+                        images::replace_img(&self.excerpt.html, &asset.href, markup);
+                }
+                None if asset.image => {
+                    let markup =
+                        |alt: &str, title: Option<&str>| images::img_html(&href, alt, title);
+                    self.content = images::replace_img(&self.content, &asset.href, markup);
+                    self.excerpt.html =
+                        images::replace_img(&self.excerpt.html, &asset.href, markup);
+                }
+                None => replace_url(&mut self.content, &mut self.excerpt, &asset.href, &href),
+            }
+            asset.href = href;
+            changed = true;
+        }
+        Ok(changed)
+    }
+
+    pub fn from_path(path: PathBuf, config: &SiteConfig) -> Result<Self> {
         if !path.exists() {
             anyhow::bail!("path does not exist: {}", path.display());
         }
@@ -66,150 +329,685 @@ impl TryFrom<PathBuf> for Markdown {
             .ok()
             .and_then(|t| t.duration_since(UNIX_EPOCH).ok().map(|d| d.as_secs()));
 
-        // 3) 读文件内容
+        // 3) 读文件内容, 按第一行判断 front matter 是 YAML, TOML 还是 JSON
         let input = fs::read_to_string(&path)
             .with_context(|| format!("failed to read: {}", path.display()))?;
-        // comrak options
-        let mut options = Options::default();
-        options.extension.front_matter_delimiter = Some("---".to_owned());
+        let format = FrontMatterFormat::detect(&input);
+        let mut options = config.markdown.comrak_options();
+        options.extension.front_matter_delimiter = format
+            .and_then(FrontMatterFormat::delimiter)
+            .map(str::to_owned);
+        let (json_front_matter, source) = match format {
+            Some(FrontMatterFormat::Json) => {
+                let (json, rest) = split_json(&input)
+                    .with_context(|| format!("Invalid JSON front matter in: {}", path.display()))?;
+                // 换成同样多的空行, 报错的行号才对得上
+                let blank = "\n".repeat(json.matches('\n').count());
+                (Some(json), format!("{blank}{rest}"))
+            }
+            _ => (None, input.clone()),
+        };
         let arena = Arena::new();
-        let root = comrak::parse_document(&arena, &input, &options);
-        let mut front_matter_string = extract_front_matter_from_ast(root)
-            .with_context(|| format!("missing front matter in: {}", path.display()))?;
-        front_matter_string = front_matter_string
-            .trim()
-            .trim_start_matches("---")
-            .trim()
-            .trim_end_matches("---")
-            .trim()
-            .to_string();
-        // serde_yaml解析front matter
-        let metadata: FrontMatter =
-            serde_yaml::from_str(&front_matter_string).with_context(|| {
-                format!(
-                    "Invalid YAML front matter in: {}\nInput YAML string is: {}",
-                    path.display(),
-                    front_matter_string
-                )
-            })?;
+        let root = comrak::parse_document(&arena, &source, &options);
+        // 没有 front matter 的笔记除了 date 都用默认值
+        let block = extract_front_matter_from_ast(root);
+        let front_matter_string = json_front_matter
+            .or_else(|| Some(format?.strip_fences(block.as_deref()?)))
+            .unwrap_or_default();
+        // 报错位置要从文件开头算
+        let lines_before = input
+            .find(front_matter_string)
+            .map_or(0, |pos| input[..pos].matches('\n').count());
+        let invalid = |e: FrontMatterError| {
+            let context = match format {
+                Some(format) => format!(
+                    "Invalid {} front matter in: {}",
+                    format.name(),
+                    path.display()
+                ),
+                None => format!("No front matter in: {}", path.display()),
+            };
+            anyhow::Error::new(e.shift_lines(lines_before)).context(context)
+        };
+        let raw: RawFrontMatter = match format {
+            None => RawFrontMatter::default(),
+            Some(format) => format.parse(front_matter_string).map_err(invalid)?,
+        };
+        let metadata = raw
+            .resolve(root, &path, config)
+            .map_err(|message| invalid(FrontMatterError::date(message, front_matter_string)))?;
+        // 4) 统计字数, 要在公式被替换成 HTML 之前
+        let stats = TextStats::from_ast(root);
+        // 5) 公式转 MathML
+        if config.markdown.math {
+            render_math(root, &path);
+        }
+        // 6) 站内链接和本地文件先换成占位符, 摘要里的也一样
+        let links = collect_post_links(root, &path);
+        let assets = collect_post_assets(root, &path);
+        // 7) 摘要
+        let excerpt = Excerpt::from_ast(root, metadata.summary.as_deref(), &options)
+            .with_context(|| format!("render excerpt failed: {}", path.display()))?;
+        // 8) 渲染 HTML
+        let highlighter = CodeHighlighter::new(&config.highlight, &path);
+        let headings = HeadingAnchors::new(config.markdown.header_ids.as_deref().unwrap_or(""));
+        let mut plugins = Plugins::default();
+        if config.highlight.enabled {
+            plugins.render.codefence_syntax_highlighter = Some(&highlighter);
+        }
+        plugins.render.heading_adapter = Some(&headings);
+        let mut content = String::new();
+        comrak::format_html_with_plugins(root, &options, &mut content, &plugins)
+            .with_context(|| format!("render html failed: {}", path.display()))?;
+        let outline = headings.into_outline();
         Ok(Self {
             path,
             modified_at_unix,
             metadata,
-            content: comrak::markdown_to_html(&input, &options),
+            outline,
+            stats,
+            excerpt,
+            links,
+            assets,
+            content,
+        })
+    }
+}
+
+impl RawFrontMatter {
+    /// Parse the date, which is required, and fill the gaps: config defaults,
+    /// the first `# heading` or file name as title.
+    fn resolve<'a>(
+        self,
+        root: &'a AstNode<'a>,
+        path: &Path,
+        config: &SiteConfig,
+    ) -> Result<FrontMatter, String> {
+        let file_stem = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let title = self
+            .title
+            .or_else(|| first_h1(root))
+            .unwrap_or_else(|| file_stem.clone());
+        // 标题里没有字母数字时退回文件名
+        let slug = [self.slug.as_deref(), Some(&title), Some(&file_stem)]
+            .into_iter()
+            .flatten()
+            .map(slugify)
+            .find(|slug| !slug.is_empty())
+            .unwrap_or_else(|| "untitled".to_owned());
+        // 不拿文件修改时间兜底: permalink 里有日期, 换台机器 checkout 地址就变了
+        let Some(date) = self.date else {
+            return Err("missing `date`, e.g. `date: 2025-01-02`".to_owned());
+        };
+        let date = date.parse()?;
+        Ok(FrontMatter {
+            title,
+            slug,
+            author: self
+                .author
+                .unwrap_or_else(|| config.default_author().to_owned()),
+            tags: self.tags.unwrap_or_else(|| config.defaults.tags.clone()),
+            date,
+            summary: self.summary,
+            draft: self.draft,
+            unlisted: self.unlisted,
+            extra: if self.extra.is_empty() {
+                serde_json::Value::Null
+            } else {
+                serde_json::Value::Object(self.extra)
+            },
         })
     }
 }
 
+fn first_h1<'a>(root: &'a AstNode<'a>) -> Option<String> {
+    root.children()
+        .find(|node| matches!(node.data.borrow().value, NodeValue::Heading(ref h) if h.level == 1))
+        .map(|node| plain_text(node).trim().to_owned())
+        .filter(|title| !title.is_empty())
+}
+
 struct BuiltMarkdown {
     markdown: Markdown,
     out_path: PathBuf,
 }
 
-fn build_markdown_and_write_json(path: &Path, dist_dir: &Path) -> anyhow::Result<BuiltMarkdown> {
+fn build_markdown(path: &Path, dist_dir: &Path, config: &SiteConfig) -> Result<BuiltMarkdown> {
     // 1) 转成 Markdown
-    let one_md: Markdown = path
-        .to_path_buf()
-        .try_into()
+    let one_md = Markdown::from_path(path.to_path_buf(), config)
         .with_context(|| format!("convert markdown failed: {}", path.display()))?;
 
-    // 2) 计算输出路径
-    let rel = path
-        .strip_prefix(std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")))
-        .unwrap_or(path);
+    // 2) 计算输出路径, 等所有文章的路径都确定不冲突了再写
+    let out_path = json_out_path(&one_md.metadata, dist_dir, config)
+        .with_context(|| format!("convert markdown failed: {}", path.display()))?;
 
-    let mut out_path = dist_dir.join(rel);
-    out_path.set_extension("json");
+    Ok(BuiltMarkdown {
+        markdown: one_md,
+        out_path,
+    })
+}
 
+/// Swap a URL written by an earlier build (or a placeholder) in `href` and `src` attributes.
+fn replace_url(content: &mut String, excerpt: &mut Excerpt, old: &str, new: &str) {
+    let (old, new) = (escape_xml(old), escape_xml(new));
+    for attr in ["href", "src"] {
+        let from = format!("{attr}=\"{old}\"");
+        let to = format!("{attr}=\"{new}\"");
+        *content = content.replace(&from, &to);
+        excerpt.html = excerpt.html.replace(&from, &to);
+    }
+}
+
+fn write_json(md: &Markdown, out_path: &Path) -> Result<()> {
     if let Some(parent) = out_path.parent() {
         fs::create_dir_all(parent)
             .with_context(|| format!("create parent dir failed: {}", parent.display()))?;
     }
+    let json = serde_json::to_string_pretty(md).context("serde_json serialize failed")?;
+    fs::write(out_path, json).with_context(|| format!("write to {} failed", out_path.display()))
+}
 
-    // 3) 写 json
-    let json = serde_json::to_string_pretty(&one_md).context("serde_json serialize failed")?;
-    fs::write(&out_path, json)
-        .with_context(|| format!("write to {} failed", out_path.display()))?;
-
-    Ok(BuiltMarkdown {
-        markdown: one_md,
-        out_path,
+/// Read back a post JSON written by an earlier build, `None` if it is unusable.
+fn load_built_markdown(out_path: &Path) -> Option<BuiltMarkdown> {
+    let json = fs::read_to_string(out_path).ok()?;
+    let markdown = serde_json::from_str(&json).ok()?;
+    Some(BuiltMarkdown {
+        markdown,
+        out_path: out_path.to_path_buf(),
     })
 }
+
+/// The first output path two posts share, with both sources, in `posts` order.
+fn output_collision<'a>(
+    posts: impl IntoIterator<Item = (&'a Path, &'a Path)>,
+) -> Option<(&'a Path, &'a Path, &'a Path)> {
+    let mut owners: HashMap<&Path, &Path> = HashMap::new();
+    for (source, out_path) in posts {
+        if let Some(first) = owners.insert(out_path, source) {
+            return Some((out_path, first, source));
+        }
+    }
+    None
+}
+
+/// `<permalink>.json` under the output dir, so the file does not move with the source.
+fn json_out_path(metadata: &FrontMatter, dist_dir: &Path, config: &SiteConfig) -> Result<PathBuf> {
+    let url = permalink::expand(&config.build.permalink, metadata.date.day, &metadata.slug)
+        .map_err(|e| anyhow::anyhow!("invalid permalink: {e}"))?;
+    Ok(dist_dir.join(format!("{url}.json")))
+}
+
 impl TryFrom<Vec<PathBuf>> for Index {
     type Error = anyhow::Error;
     fn try_from(paths: Vec<PathBuf>) -> anyhow::Result<Self> {
-        let dist_dir = PathBuf::from("dist");
-        fs::create_dir_all(&dist_dir).context("failed to create dist/")?;
-        let mut paragraph_under_certain_topic: HashMap<String, Vec<String>> = HashMap::new();
+        Self::build(paths, &SiteConfig::default(), &BuildOptions::default())
+    }
+}
+
+impl Index {
+    pub fn markdowns(&self) -> &[Markdown] {
+        &self.markdowns
+    }
+
+    pub fn summary(&self) -> &BuildSummary {
+        &self.summary
+    }
+
+    /// Convert every markdown under `paths` into the configured output dir, then write `index.json` there.
+    /// Posts whose source and config are unchanged since the last build are read back from their JSON.
+    pub fn build(
+        paths: Vec<PathBuf>,
+        config: &SiteConfig,
+        build_options: &BuildOptions,
+    ) -> anyhow::Result<Self> {
+        let dist_dir = config.build.output.as_path();
+        let fingerprint = config.fingerprint();
+        fs::create_dir_all(dist_dir)
+            .with_context(|| format!("failed to create {}", dist_dir.display()))?;
+        let mut manifest = BuildManifest::load(dist_dir);
+        let mut summary = BuildSummary {
+            removed: manifest.prune_missing_sources(),
+            ..Default::default()
+        };
+        // 1) 发现: 路径先排好序, 保证输出与线程调度无关
+        let md_paths = collect_markdown_paths(&paths);
+        // 2) 并行转换, collect 保持输入顺序
+        let mut converted: Vec<(PathBuf, String, BuiltMarkdown, bool)> = md_paths
+            .into_par_iter()
+            .map(|md_path| {
+                let input = fs::read(&md_path)
+                    .with_context(|| format!("failed to read: {}", md_path.display()))?;
+                let hash = source_hash(&input, &fingerprint);
+                let reused = manifest
+                    .fresh_output(&md_path, &hash)
+                    .filter(|_| !build_options.force)
+                    .and_then(load_built_markdown);
+                let (built_md, is_reused) = match reused {
+                    Some(built_md) => (built_md, true),
+                    None => (build_markdown(&md_path, dist_dir, config)?, false),
+                };
+                Ok((md_path, hash, built_md, is_reused))
+            })
+            .collect::<Result<_>>()?;
+        // 草稿和定时文章也算: 它们和已发布的文章写到同一个文件时, 删掉自己的输出就删掉了别人的
+        if let Some((out_path, first, second)) = output_collision(
+            converted
+                .iter()
+                .map(|(md_path, _, built_md, _)| (md_path.as_path(), built_md.out_path.as_path())),
+        ) {
+            anyhow::bail!(
+                "{} and {} both have the permalink `{}`, set a different `slug:` in one of them",
+                first.display(),
+                second.display(),
+                post_url(&relative_json_path(out_path, dist_dir))
+            );
+        }
+        // 只差大小写或符号的 tag 会写到同一个 tags/<slug>/ 下, 后写的会盖掉先写的
+        let mut tag_dirs = TagDirs::default();
+        for (md_path, _, built_md, _) in &converted {
+            for tag in &built_md.markdown.metadata.tags {
+                if let Err(message) = tag_dirs.claim(tag, md_path) {
+                    anyhow::bail!("{}: {message}", md_path.display());
+                }
+            }
+        }
+        let now = Utc::now();
+        let timezone = config.build.timezone_offset();
+        let is_published =
+            |metadata: &FrontMatter| build_options.publishes(metadata, timezone, now);
+        // 3) 站内链接: 先记下每篇文章的 permalink 和标题锚点, 再把链接指过去; 本地文件复制到 assets/
+        let targets: HashMap<PathBuf, LinkTarget> = converted
+            .iter()
+            .filter(|(_, _, built_md, _)| is_published(&built_md.markdown.metadata))
+            .filter_map(|(md_path, _, built_md, _)| {
+                let url = post_url(&relative_json_path(&built_md.out_path, dist_dir));
+                Some((
+                    fs::canonicalize(md_path).ok()?,
+                    built_md.markdown.link_target(url),
+                ))
+            })
+            .collect();
+        let mut broken_links = Vec::new();
+        let mut assets = AssetStore::new(dist_dir, &config.images);
+        for (_, _, built_md, is_reused) in &mut converted {
+            let markdown = &mut built_md.markdown;
+            if !is_published(&markdown.metadata) {
+                for asset in &markdown.assets {
+                    assets.hold_back(&asset.source);
+                }
+                continue;
+            }
+            let links_changed = markdown.resolve_links(&targets, &config.site, &mut broken_links);
+            let assets_changed = markdown.resolve_assets(&mut assets, &config.site)?;
+            if !*is_reused || links_changed || assets_changed {
+                write_json(markdown, &built_md.out_path)?;
+            }
+        }
+        summary.unreferenced_assets = assets.unreferenced(&paths);
+        // 失败的构建可能留下了写了一半的 JSON, 这次转换过的文章下次要重新转换
+        let converted_now: Vec<PathBuf> = converted
+            .iter()
+            .filter(|(_, _, _, is_reused)| !is_reused)
+            .map(|(md_path, ..)| md_path.clone())
+            .collect();
+        if !broken_links.is_empty() {
+            manifest.forget_all(&converted_now);
+            manifest.save(dist_dir)?;
+            anyhow::bail!(
+                "{} broken link(s):\n{}",
+                broken_links.len(),
+                broken_links.join("\n")
+            );
+        }
+        // 4) 汇总
+        let mut paragraph_under_certain_topic: BTreeMap<String, Vec<String>> = BTreeMap::new();
         let mut markdowns: Vec<Markdown> = Vec::new();
         let mut table_of_content: Vec<TableOfContentItem> = Vec::new();
-        for path in paths {
-            if !path.exists() {
-                eprintln!("Skip: {} (not exists)", path.display());
+        let mut unlisted: Vec<TableOfContentItem> = Vec::new();
+        let mut json_paths: Vec<String> = Vec::new();
+        let outputs_before = manifest.outputs();
+        let published_outputs: HashSet<PathBuf> = converted
+            .iter()
+            .filter(|(_, _, built_md, _)| is_published(&built_md.markdown.metadata))
+            .map(|(_, _, built_md, _)| built_md.out_path.clone())
+            .collect();
+        for (md_path, hash, built_md, is_reused) in converted {
+            let metadata = &built_md.markdown.metadata;
+            let held_back = if metadata.draft && !build_options.drafts {
+                Some(&mut summary.skipped_drafts)
+            } else if !build_options.future && metadata.date.publish_at(timezone) > now {
+                Some(&mut summary.scheduled)
+            } else {
+                None
+            };
+            if let Some(held_back) = held_back {
+                // 草稿和定时文章不留任何输出, 之前发布过的也一并删掉, 除非那是别的文章的
+                if !published_outputs.contains(&built_md.out_path)
+                    && let Err(e) = fs::remove_file(&built_md.out_path)
+                    && e.kind() != std::io::ErrorKind::NotFound
+                {
+                    eprintln!("Failed to remove {}: {e}", built_md.out_path.display());
+                }
+                manifest.forget(&md_path);
+                held_back.push(md_path);
                 continue;
             }
-            if path.is_file() && is_markdown(&path) {
-                let built_md = build_markdown_and_write_json(&path, &dist_dir)?;
-                let title = built_md.markdown.metadata.title.clone();
-                let rel_path = relative_json_path(&built_md.out_path, &dist_dir);
-                for tag in &built_md.markdown.metadata.tags {
-                    paragraph_under_certain_topic
-                        .entry(tag.clone())
-                        .or_default()
-                        .push(title.clone());
-                }
-                table_of_content.push(TableOfContentItem {
-                    title,
-                    path: rel_path,
-                    date: built_md.markdown.metadata.date,
-                });
-                markdowns.push(built_md.markdown);
+            if is_reused {
+                summary.unchanged += 1;
+            } else {
+                summary.converted += 1;
             }
-            for entry in walkdir::WalkDir::new(&path).follow_links(true) {
-                let entry = match entry {
-                    Ok(e) => e,
-                    Err(e) => {
-                        eprintln!("Walk error under {}: {e}", path.display());
-                        continue;
-                    }
-                };
-                if !entry.file_type().is_file() {
-                    continue;
-                }
-                let md_path = entry.path();
-                if !is_markdown(md_path) {
-                    continue;
-                }
-                let built_md = build_markdown_and_write_json(md_path, &dist_dir)?;
-                let title = built_md.markdown.metadata.title.clone();
-                let rel_path = relative_json_path(&built_md.out_path, &dist_dir);
+            manifest.record(md_path.clone(), hash, built_md.out_path.clone());
+            let title = built_md.markdown.metadata.title.clone();
+            let rel_path = relative_json_path(&built_md.out_path, dist_dir);
+            let item = TableOfContentItem {
+                title,
+                url: post_url(&rel_path),
+                path: rel_path.clone(),
+                date: built_md.markdown.metadata.date.day,
+                tags: built_md.markdown.metadata.tags.clone(),
+                stats: built_md.markdown.stats,
+                excerpt: built_md.markdown.excerpt.clone(),
+            };
+            if built_md.markdown.metadata.unlisted {
+                summary.unlisted.push(md_path);
+                unlisted.push(item);
+            } else {
                 for tag in &built_md.markdown.metadata.tags {
                     paragraph_under_certain_topic
                         .entry(tag.clone())
                         .or_default()
-                        .push(title.clone());
+                        .push(item.path.clone());
                 }
-                table_of_content.push(TableOfContentItem {
-                    title,
-                    path: rel_path,
-                    date: built_md.markdown.metadata.date,
-                });
-                markdowns.push(built_md.markdown);
+                table_of_content.push(item);
             }
+            markdowns.push(built_md.markdown);
+            json_paths.push(rel_path);
         }
-        table_of_content.sort_by(|a, b| b.date.cmp(&a.date));
+        // 改了 slug 或 permalink 的文章, 旧的 JSON 要删掉
+        manifest.remove_moved_outputs(&outputs_before);
+        // stable sort: posts sharing a date keep their path order
+        table_of_content.sort_by_key(|item| std::cmp::Reverse(item.date));
         let index = Self {
+            site: config.site.clone(),
             table_of_content,
+            prerendered: config.prerender.enabled,
             paragraph_under_certain_topic,
             markdowns,
+            unlisted,
+            summary,
         };
+        if config.feed.enabled {
+            index.write_feeds(&json_paths, dist_dir, config)?;
+        }
+        // feed 会清空 tags/, 所以静态页面在它之后写
+        let pages = if config.prerender.enabled {
+            index.write_pages(&json_paths, dist_dir, config)?
+        } else {
+            BTreeSet::new()
+        };
+        manifest.replace_pages(pages);
+        manifest.replace_assets(assets.written());
+        manifest.save(dist_dir)?;
+        let with_sitemap = config.sitemap.enabled
+            && write_sitemap(dist_dir, &config.site, &index.sitemap_entries(&json_paths))?;
+        if config.robots.enabled {
+            write_robots(dist_dir, &config.site, &config.robots, with_sitemap)?;
+        }
+        let search_inputs = index
+            .markdowns
+            .iter()
+            .zip(json_paths)
+            .filter(|(md, _)| !md.metadata.unlisted)
+            .map(|(md, path)| SearchInput {
+                path,
+                title: &md.metadata.title,
+                content: &md.content,
+            })
+            .collect();
+        SearchIndex::build(search_inputs).write(dist_dir)?;
+        let css_path = dist_dir.join(HIGHLIGHT_CSS_FILE);
+        if config.highlight.enabled && config.highlight.mode == HighlightMode::Css {
+            let css = highlight_css(&config.highlight).context("generate highlight css failed")?;
+            fs::write(&css_path, css)
+                .with_context(|| format!("write to {} failed", css_path.display()))?;
+        }
         let index_path = dist_dir.join("index.json");
         let index_json = serde_json::to_string_pretty(&index).context("serialize index failed")?;
         fs::write(&index_path, index_json)
             .with_context(|| format!("write to {} failed", index_path.display()))?;
         Ok(index)
     }
+
+    /// Site relative address a post can be opened at from outside: its page, or the web app
+    /// pointed at its JSON when no pages are written.
+    fn post_link(&self, item: &TableOfContentItem) -> String {
+        if self.prerendered {
+            item.url.clone()
+        } else {
+            format!("?post={}", percent_encode_path(&item.path))
+        }
+    }
+
+    /// Site wide feeds from the sorted toc, plus one per topic when `feed.tags` is set.
+    fn write_feeds(
+        &self,
+        json_paths: &[String],
+        dist_dir: &Path,
+        config: &SiteConfig,
+    ) -> Result<()> {
+        // 旧 tag 的 feed 不再保留
+        let tags_root = dist_dir.join(TAG_DIR);
+        if tags_root.exists() {
+            fs::remove_dir_all(&tags_root)
+                .with_context(|| format!("failed to remove {}", tags_root.display()))?;
+        }
+        // 相对地址的 id 和 link 不是合法的 Atom/RSS, 和 sitemap 一样跳过
+        if !has_absolute_base_url(&config.site) {
+            eprintln!("warning: `site.base_url` is not an absolute http(s) URL, skipping feeds");
+            for file in [ATOM_FILE, RSS_FILE, JSON_FEED_FILE] {
+                let path = dist_dir.join(file);
+                if let Err(e) = fs::remove_file(&path)
+                    && e.kind() != std::io::ErrorKind::NotFound
+                {
+                    eprintln!("Failed to remove {}: {e}", path.display());
+                }
+            }
+            return Ok(());
+        }
+        let by_path = self.markdowns_by_path(json_paths);
+        let entries: Vec<FeedEntry> = self
+            .table_of_content
+            .iter()
+            .filter_map(|item| {
+                let md = by_path.get(item.path.as_str())?;
+                Some(FeedEntry {
+                    title: &item.title,
+                    author: &md.metadata.author,
+                    url: self.post_link(item),
+                    date: item.date,
+                    summary: &item.excerpt.text,
+                    content: &md.content,
+                    tags: &md.metadata.tags,
+                })
+            })
+            .collect();
+        let site_title = if config.site.title.is_empty() {
+            "Posts"
+        } else {
+            config.site.title.as_str()
+        };
+        Feed {
+            title: site_title.to_owned(),
+            dir: String::new(),
+            entries: entries.iter().collect(),
+        }
+        .write(dist_dir, &config.site, &config.feed)?;
+
+        if !config.feed.tags {
+            return Ok(());
+        }
+        for tag in self.paragraph_under_certain_topic.keys() {
+            Feed {
+                title: format!("{site_title} · {tag}"),
+                dir: tag_dir(tag),
+                entries: entries
+                    .iter()
+                    .filter(|entry| entry.tags.contains(tag))
+                    .collect(),
+            }
+            .write(dist_dir, &config.site, &config.feed)?;
+        }
+        Ok(())
+    }
+
+    /// One HTML page per post, the archive and one per tag; returns every file written.
+    fn write_pages(
+        &self,
+        json_paths: &[String],
+        dist_dir: &Path,
+        config: &SiteConfig,
+    ) -> Result<BTreeSet<PathBuf>> {
+        let shell = PageShell::load(config);
+        let by_path = self.markdowns_by_path(json_paths);
+        let mut pages = BTreeSet::new();
+        for item in self.table_of_content.iter().chain(&self.unlisted) {
+            let Some(md) = by_path.get(item.path.as_str()) else {
+                continue;
+            };
+            let html = shell.post_page(
+                config,
+                &PostPage {
+                    title: &item.title,
+                    path: &item.path,
+                    author: &md.metadata.author,
+                    date: item.date,
+                    url: &item.url,
+                    stats: md.stats,
+                    excerpt: &md.excerpt,
+                    outline: &md.outline,
+                    content: &md.content,
+                },
+            );
+            pages.insert(write_page(dist_dir, &item.url, &html)?);
+        }
+        let archive: Vec<ListedPost> = self
+            .table_of_content
+            .iter()
+            .map(TableOfContentItem::listed)
+            .collect();
+        let html = shell.listing_page(config, None, ARCHIVE_URL, &archive);
+        pages.insert(write_page(dist_dir, ARCHIVE_URL, &html)?);
+        for tag in self.paragraph_under_certain_topic.keys() {
+            let posts: Vec<ListedPost> = self
+                .table_of_content
+                .iter()
+                .filter(|item| item.tags.contains(tag))
+                .map(TableOfContentItem::listed)
+                .collect();
+            let url = format!("{}/", tag_dir(tag));
+            let html = shell.listing_page(config, Some(tag), &url, &posts);
+            pages.insert(write_page(dist_dir, &url, &html)?);
+        }
+        Ok(pages)
+    }
+
+    /// `json_paths` must be in the same order as `self.markdowns`.
+    fn markdowns_by_path<'a>(&'a self, json_paths: &'a [String]) -> HashMap<&'a str, &'a Markdown> {
+        json_paths
+            .iter()
+            .map(String::as_str)
+            .zip(&self.markdowns)
+            .collect()
+    }
+
+    /// Home, archive, every post and every tag page; listing pages take the newest `lastmod` of their posts.
+    fn sitemap_entries(&self, json_paths: &[String]) -> Vec<SitemapEntry> {
+        let by_path = self.markdowns_by_path(json_paths);
+        // 文件修改时间优先, 没有再用 front matter 里的日期
+        let post_lastmod = |item: &TableOfContentItem| {
+            by_path
+                .get(item.path.as_str())
+                .and_then(|md| md.modified_at_unix)
+                .and_then(|secs| chrono::DateTime::from_timestamp(secs as i64, 0))
+                .map_or(item.date, |time| time.date_naive())
+        };
+        let site_lastmod = self
+            .table_of_content
+            .iter()
+            .map(post_lastmod)
+            .max()
+            .unwrap_or_default();
+        let mut entries = vec![
+            SitemapEntry {
+                url: String::new(),
+                lastmod: site_lastmod,
+            },
+            SitemapEntry {
+                url: ARCHIVE_URL.to_owned(),
+                lastmod: site_lastmod,
+            },
+        ];
+        entries.extend(self.table_of_content.iter().map(|item| SitemapEntry {
+            url: item.url.clone(),
+            lastmod: post_lastmod(item),
+        }));
+        for (tag, paths) in &self.paragraph_under_certain_topic {
+            let lastmod = self
+                .table_of_content
+                .iter()
+                .filter(|item| paths.contains(&item.path))
+                .map(post_lastmod)
+                .max()
+                .unwrap_or_default();
+            entries.push(SitemapEntry {
+                url: format!("{}/", tag_dir(tag)),
+                lastmod,
+            });
+        }
+        entries
+    }
+}
+
+/// `notes/a.json` is shown at `notes/a`.
+fn post_url(json_path: &str) -> String {
+    json_path
+        .strip_suffix(".json")
+        .unwrap_or(json_path)
+        .to_owned()
+}
+
+/// Expand files and directories in `paths` into the markdown files they contain,
+/// sorted and without duplicates so overlapping roots yield each file once.
+pub fn collect_markdown_paths(paths: &[PathBuf]) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    for path in paths {
+        if !path.exists() {
+            eprintln!("Skip: {} (not exists)", path.display());
+            continue;
+        }
+        for entry in walkdir::WalkDir::new(path).follow_links(true) {
+            let entry = match entry {
+                Ok(e) => e,
+                Err(e) => {
+                    eprintln!("Walk error under {}: {e}", path.display());
+                    continue;
+                }
+            };
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            if is_markdown(entry.path()) {
+                found.push(entry.into_path());
+            }
+        }
+    }
+    found.sort();
+    found.dedup();
+    found
 }
 
 fn extract_front_matter_from_ast<'a>(root: &'a comrak::nodes::AstNode<'a>) -> Option<String> {
@@ -222,7 +1020,71 @@ fn extract_front_matter_from_ast<'a>(root: &'a comrak::nodes::AstNode<'a>) -> Op
     None
 }
 
+/// Joined with `/` whatever the platform, it ends up in URLs.
 fn relative_json_path(path: &Path, dist_dir: &Path) -> String {
     let rel = path.strip_prefix(dist_dir).unwrap_or(path);
-    rel.to_string_lossy().into_owned()
+    rel.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_first_shared_output() {
+        let posts = [
+            (Path::new("a.md"), Path::new("dist/hello.json")),
+            (Path::new("b.md"), Path::new("dist/other.json")),
+            (Path::new("c.md"), Path::new("dist/hello.json")),
+        ];
+        assert_eq!(
+            output_collision(posts),
+            Some((
+                Path::new("dist/hello.json"),
+                Path::new("a.md"),
+                Path::new("c.md")
+            ))
+        );
+        assert_eq!(output_collision(posts[..2].iter().copied()), None);
+    }
+
+    #[test]
+    fn a_draft_sharing_a_slug_never_removes_the_published_post() {
+        let root = std::env::temp_dir().join(format!("sitegen-collision-{}", std::process::id()));
+        let notes = root.join("notes");
+        fs::create_dir_all(&notes).unwrap();
+        let post =
+            |date: &str, extra: &str| format!("---\ntitle: Hello\ndate: {date}\n{extra}---\nhi\n");
+        fs::write(notes.join("a.md"), post("2024-01-05", "")).unwrap();
+        let mut config = SiteConfig::default();
+        config.build.output = root.join("dist");
+        config.build.permalink = "/{slug}".to_owned();
+        config.prerender.enabled = false;
+        let build = |config: &SiteConfig| {
+            Index::build(vec![notes.clone()], config, &BuildOptions::default())
+        };
+        let published = config.build.output.join("hello.json");
+
+        build(&config).unwrap();
+        assert!(published.is_file());
+        fs::write(notes.join("b.md"), post("2024-01-06", "draft: true\n")).unwrap();
+        let err = build(&config).unwrap_err();
+        assert!(
+            format!("{err:#}").contains("both have the permalink"),
+            "{err:#}"
+        );
+        assert!(published.is_file());
+        fs::write(
+            notes.join("b.md"),
+            post("2024-01-06", "draft: true\nslug: hello-2\n"),
+        )
+        .unwrap();
+        build(&config).unwrap();
+        assert!(published.is_file());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
 }
@@ -0,0 +1,45 @@
+use comrak::nodes::{AstNode, NodeMath, NodeValue};
+use math_core::{LatexToMathML, MathCoreConfig, MathDisplay};
+use std::{path::Path, sync::LazyLock};
+
+/// Class on the fallback `<span>` that shows the raw LaTeX when conversion fails.
+const MATH_ERROR_CLASS: &str = "math-error";
+
+static CONVERTER: LazyLock<LatexToMathML> = LazyLock::new(|| {
+    LatexToMathML::new(MathCoreConfig::default()).expect("default math-core config is valid")
+});
+
+/// Replace every `$..$` / `$$..$$` node under `root` with MathML so the web app needs no math library.
+/// Formulas that fail to convert are kept as escaped source and reported as a warning.
+pub fn render_math<'a>(root: &'a AstNode<'a>, path: &Path) {
+    for node in root.descendants() {
+        let mut ast = node.data.borrow_mut();
+        let NodeValue::Math(NodeMath {
+            display_math,
+            ref literal,
+            ..
+        }) = ast.value
+        else {
+            continue;
+        };
+        let display = if display_math {
+            MathDisplay::Block
+        } else {
+            MathDisplay::Inline
+        };
+        let html = match CONVERTER.convert_with_local_state(literal, display) {
+            Ok(result) => result.mathml,
+            Err(err) => {
+                eprintln!(
+                    "warning: {}:{}: {}",
+                    path.display(),
+                    ast.sourcepos.start.line,
+                    err.error_message()
+                );
+                // `Inline` keeps the fallback a `<span>`, display math still sits inside a `<p>`
+                err.to_html(literal, MathDisplay::Inline, Some(MATH_ERROR_CLASS))
+            }
+        };
+        ast.value = NodeValue::Raw(html);
+    }
+}
@@ -0,0 +1,58 @@
+use comrak::{
+    Anchorizer,
+    adapters::{HeadingAdapter, HeadingMeta},
+    nodes::Sourcepos,
+};
+use serde::{Deserialize, Serialize};
+use std::{fmt, sync::Mutex};
+
+/// One heading of a post, in document order.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutlineItem {
+    pub level: u8,
+    pub text: String,
+    pub anchor: String,
+}
+
+/// Gives every heading a GFM style slug `id` and records it in the outline while rendering.
+/// Slugs only depend on the heading texts, so they stay stable across builds.
+pub struct HeadingAnchors<'a> {
+    prefix: &'a str,
+    state: Mutex<(Anchorizer, Vec<OutlineItem>)>,
+}
+
+impl<'a> HeadingAnchors<'a> {
+    pub fn new(prefix: &'a str) -> Self {
+        Self {
+            prefix,
+            state: Mutex::new((Anchorizer::new(), Vec::new())),
+        }
+    }
+
+    pub fn into_outline(self) -> Vec<OutlineItem> {
+        self.state.into_inner().expect("outline poisoned").1
+    }
+}
+
+impl HeadingAdapter for HeadingAnchors<'_> {
+    fn enter(
+        &self,
+        output: &mut dyn fmt::Write,
+        heading: &HeadingMeta,
+        _sourcepos: Option<Sourcepos>,
+    ) -> fmt::Result {
+        let mut state = self.state.lock().expect("outline poisoned");
+        let anchor = format!("{}{}", self.prefix, state.0.anchorize(&heading.content));
+        write!(output, "<h{} id=\"{anchor}\">", heading.level)?;
+        state.1.push(OutlineItem {
+            level: heading.level,
+            text: heading.content.trim().to_owned(),
+            anchor,
+        });
+        Ok(())
+    }
+
+    fn exit(&self, output: &mut dyn fmt::Write, heading: &HeadingMeta) -> fmt::Result {
+        writeln!(output, "</h{}>", heading.level)
+    }
+}
@@ -0,0 +1,172 @@
+use chrono::{Datelike, NaiveDate};
+
+use crate::assets::ASSET_DIR;
+use crate::feed::{JSON_FEED_FILE, TAG_DIR};
+use crate::search::SEARCH_INDEX_FILE;
+use crate::sitemap::ARCHIVE_URL;
+
+/// Used when `build.permalink` is not set.
+pub const DEFAULT_PERMALINK: &str = "/{year}/{month}/{slug}";
+
+const PLACEHOLDERS: [&str; 4] = ["year", "month", "day", "slug"];
+
+/// Lowercase letters and digits (CJK included) joined by single `-`, so never a path separator or `..`.
+/// Non-ASCII letters are kept as they are: HTML and feeds carry them as IRIs, browsers encode them
+/// on request, and only the sitemap percent-encodes them.
+pub fn slugify(text: &str) -> String {
+    let mut slug = String::with_capacity(text.len());
+    for c in text.chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.truncate(slug.trim_end_matches('-').len());
+    slug
+}
+
+/// Reject unknown `{placeholders}` and patterns without `{slug}`, which could not keep posts apart.
+pub fn validate(pattern: &str) -> Result<(), String> {
+    let mut rest = pattern;
+    while let Some(start) = rest.find('{') {
+        let Some(len) = rest[start..].find('}') else {
+            return Err(format!("unclosed `{{` in `{pattern}`"));
+        };
+        let name = &rest[start + 1..start + len];
+        if !PLACEHOLDERS.contains(&name) {
+            return Err(format!(
+                "unknown placeholder `{{{name}}}` in `{pattern}`, expected one of {{year}}, {{month}}, {{day}}, {{slug}}"
+            ));
+        }
+        rest = &rest[start + len + 1..];
+    }
+    if !pattern.contains("{slug}") {
+        return Err(format!("`{pattern}` has no {{slug}}"));
+    }
+    if pattern.contains('\\')
+        || pattern
+            .split('/')
+            .any(|segment| matches!(segment, "." | ".."))
+    {
+        return Err(format!(
+            "`{pattern}` has a `.` or `..` segment or a `\\`, posts must stay inside the output dir"
+        ));
+    }
+    Ok(())
+}
+
+/// Site relative address of a post, e.g. `2025/01/hello-world`, always with `/` separators.
+/// Fails for slugs that would leave the output dir and for addresses the build uses itself.
+pub fn expand(pattern: &str, day: NaiveDate, slug: &str) -> Result<String, String> {
+    if slug.is_empty() || slug.contains(['/', '\\']) || matches!(slug, "." | "..") {
+        return Err(format!("slug `{slug}` is not a single path segment"));
+    }
+    let url = pattern
+        .replace("{year}", &day.year().to_string())
+        .replace("{month}", &format!("{:02}", day.month()))
+        .replace("{day}", &format!("{:02}", day.day()))
+        .replace("{slug}", slug);
+    let segments: Vec<&str> = url
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect();
+    if segments
+        .iter()
+        .any(|segment| matches!(*segment, "." | ".."))
+    {
+        return Err(format!("`{url}` has a `.` or `..` segment"));
+    }
+    let url = segments.join("/");
+    let reserved_dirs = [TAG_DIR, ASSET_DIR, ARCHIVE_URL.trim_end_matches('/')];
+    if let Some(dir) = segments
+        .first()
+        .filter(|first| reserved_dirs.contains(first))
+    {
+        return Err(format!(
+            "`{url}` is under `{dir}/`, which the build writes itself"
+        ));
+    }
+    let json = format!("{url}.json");
+    if ["index.json", JSON_FEED_FILE, SEARCH_INDEX_FILE].contains(&json.as_str()) {
+        return Err(format!(
+            "`{url}` would overwrite `{json}`, which the build writes itself"
+        ));
+    }
+    Ok(url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 1, 9).unwrap()
+    }
+
+    #[test]
+    fn slugifies_ascii() {
+        assert_eq!(slugify("Hello, World!"), "hello-world");
+        assert_eq!(slugify("  --Rust 2024 -- edition--  "), "rust-2024-edition");
+        assert_eq!(slugify("../../etc/passwd"), "etc-passwd");
+        assert_eq!(slugify("?!"), "");
+    }
+
+    #[test]
+    fn keeps_cjk_in_slugs() {
+        assert_eq!(slugify("你好，世界"), "你好-世界");
+        assert_eq!(slugify("Rust 入门 (一)"), "rust-入门-一");
+        assert_eq!(slugify("日本語のテキスト"), "日本語のテキスト");
+    }
+
+    #[test]
+    fn validates_patterns() {
+        assert!(validate(DEFAULT_PERMALINK).is_ok());
+        assert!(validate("/posts/{slug}/").is_ok());
+        assert!(validate("/{year}/{title}").is_err());
+        assert!(validate("/{year}/{slug").is_err());
+        assert!(validate("/{year}/{month}").is_err());
+    }
+
+    #[test]
+    fn rejects_patterns_leaving_the_output_dir() {
+        assert!(validate("/../{slug}").is_err());
+        assert!(validate("/{year}/./{slug}").is_err());
+        assert!(validate("\\{slug}").is_err());
+    }
+
+    #[test]
+    fn expands_placeholders() {
+        assert_eq!(
+            expand(DEFAULT_PERMALINK, day(), "hello").unwrap(),
+            "2025/01/hello"
+        );
+        assert_eq!(
+            expand("/{year}/{month}/{day}/{slug}/", day(), "你好").unwrap(),
+            "2025/01/09/你好"
+        );
+        assert_eq!(expand("//posts//{slug}", day(), "a").unwrap(), "posts/a");
+    }
+
+    #[test]
+    fn rejects_slugs_that_are_not_one_segment() {
+        for slug in ["", ".", "..", "a/b", "..\\x", "../../etc"] {
+            assert!(expand(DEFAULT_PERMALINK, day(), slug).is_err(), "{slug}");
+        }
+    }
+
+    #[test]
+    fn rejects_addresses_the_build_writes() {
+        for (pattern, slug) in [
+            ("/{slug}", "index"),
+            ("/{slug}", "feed"),
+            ("/{slug}", "search-index"),
+            ("/tags/{slug}", "rust"),
+            ("/{slug}/x", "assets"),
+            ("/archive/{slug}", "a"),
+        ] {
+            assert!(expand(pattern, day(), slug).is_err(), "{pattern} {slug}");
+        }
+        assert!(expand("/{slug}", day(), "indexes").is_ok());
+    }
+}
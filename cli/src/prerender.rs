@@ -0,0 +1,309 @@
+use anyhow::{Context, Result};
+use chrono::{Datelike, NaiveDate};
+use std::{
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::config::{HighlightMode, SiteConfig};
+use crate::excerpt::Excerpt;
+use crate::feed::{ATOM_FILE, absolute_url, escape_xml as escape, has_absolute_base_url};
+use crate::highlight::HIGHLIGHT_CSS_FILE;
+use crate::outline::OutlineItem;
+use crate::stats::TextStats;
+
+/// Id of the element holding the prerendered page, the web app removes it once it has taken over.
+pub const PRERENDER_ID: &str = "prerender";
+
+/// Used when no app shell is configured: a plain page, readable without the web app.
+const FALLBACK_SHELL: &str = r#"<!doctype html>
+<html>
+    <head>
+        <meta charset="utf-8" />
+        <meta name="viewport" content="width=device-width,initial-scale=1" />
+    </head>
+    <body>
+    </body>
+</html>
+"#;
+
+/// The page every prerendered view is poured into, normally Trunk's `index.html` so the app boots on top.
+pub struct PageShell {
+    template: String,
+}
+
+pub struct PostPage<'a> {
+    pub title: &'a str,
+    /// The post JSON, lets the web app open posts it cannot find in the toc
+    pub path: &'a str,
+    pub author: &'a str,
+    pub date: NaiveDate,
+    pub url: &'a str,
+    pub stats: TextStats,
+    pub excerpt: &'a Excerpt,
+    pub outline: &'a [OutlineItem],
+    pub content: &'a str,
+}
+
+/// A post as listed on the archive and tag pages, newest first.
+pub struct ListedPost<'a> {
+    pub title: &'a str,
+    pub url: &'a str,
+    pub date: NaiveDate,
+    pub stats: TextStats,
+    pub excerpt: &'a str,
+}
+
+impl PageShell {
+    /// Read `prerender.shell`, falling back to a bare page with a warning when it is missing.
+    pub fn load(config: &SiteConfig) -> Self {
+        let template = config.prerender.shell.as_ref().and_then(|path| {
+            fs::read_to_string(path)
+                .inspect_err(|e| {
+                    eprintln!(
+                        "warning: app shell {} unreadable ({e}), pages are written without the web app",
+                        path.display()
+                    )
+                })
+                .ok()
+        });
+        Self {
+            template: template.unwrap_or_else(|| FALLBACK_SHELL.to_owned()),
+        }
+    }
+
+    /// Swap in our `<title>`, add `head` before `</head>` and the page right after `<body>`.
+    /// `data` becomes `data-*` attributes telling the web app what the page shows.
+    fn render(&self, title: &str, head: &str, body: &str, data: &[(&str, &str)]) -> String {
+        let mut html = self.template.clone();
+        if let Some(start) = html.find("<title>")
+            && let Some(len) = html[start..].find("</title>")
+        {
+            html.replace_range(start..start + len + "</title>".len(), "");
+        }
+        let head = format!("<title>{}</title>\n{head}", escape(title));
+        match html.find("</head>") {
+            Some(pos) => html.insert_str(pos, &head),
+            None => html.insert_str(0, &head),
+        }
+        let data: String = data
+            .iter()
+            .map(|(name, value)| format!(" data-{name}=\"{}\"", escape(value)))
+            .collect();
+        let body = format!("<div id=\"{PRERENDER_ID}\"{data}>\n{body}</div>\n");
+        match html
+            .find("<body")
+            .and_then(|pos| html[pos..].find('>').map(|end| pos + end + 1))
+        {
+            Some(pos) => html.insert_str(pos, &body),
+            None => html.push_str(&body),
+        }
+        html
+    }
+
+    pub fn post_page(&self, config: &SiteConfig, post: &PostPage) -> String {
+        let site_title = &config.site.title;
+        let title = page_title(post.title, site_title);
+        let mut head = common_head(config, post.url, &post.excerpt.text);
+        let _ = writeln!(head, "<meta property=\"og:type\" content=\"article\" />");
+        let _ = writeln!(
+            head,
+            "<meta property=\"og:title\" content=\"{}\" />",
+            escape(post.title)
+        );
+
+        let mut body = String::from("<main class=\"page\">\n<header class=\"header\">\n<div>\n");
+        let _ = writeln!(body, "<h1 class=\"title\">{}</h1>", escape(post.title));
+        let _ = writeln!(
+            body,
+            "<p class=\"subtitle\">{} · {} · {}</p>",
+            escape(post.author),
+            post.date.format("%Y-%m-%d"),
+            stats_label(post.stats)
+        );
+        body.push_str("</div>\n");
+        let _ = writeln!(
+            body,
+            "<a class=\"home-button\" href=\"{}\">Home</a>",
+            escape(&absolute_url(&config.site, ""))
+        );
+        body.push_str("</header>\n<hr class=\"divider\" />\n<div class=\"post-layout\">\n");
+        let _ = writeln!(
+            body,
+            "<section class=\"card article\">\n{}</section>",
+            post.content
+        );
+        if !post.outline.is_empty() {
+            let min_level = post
+                .outline
+                .iter()
+                .map(|item| item.level)
+                .min()
+                .unwrap_or(1);
+            body.push_str("<nav class=\"outline-nav\">\n<p class=\"outline-title\">On this page</p>\n<ul class=\"outline-list\">\n");
+            for item in post.outline {
+                let _ = writeln!(
+                    body,
+                    "<li class=\"outline-item outline-depth-{}\"><a href=\"#{}\">{}</a></li>",
+                    item.level - min_level,
+                    escape(&item.anchor),
+                    escape(&item.text)
+                );
+            }
+            body.push_str("</ul>\n</nav>\n");
+        }
+        body.push_str("</div>\n</main>\n");
+        self.render(&title, &head, &body, &[("post", post.path)])
+    }
+
+    /// The archive, or the page of `tag`: posts grouped by month like `ArchiveView`.
+    pub fn listing_page(
+        &self,
+        config: &SiteConfig,
+        tag: Option<&str>,
+        url: &str,
+        posts: &[ListedPost],
+    ) -> String {
+        let heading = match tag {
+            Some(tag) => format!("# {tag}"),
+            None => "Archive".to_owned(),
+        };
+        let heading = heading.as_str();
+        let title = page_title(heading, &config.site.title);
+        let description = format!("{heading} · {} 篇", posts.len());
+        let head = common_head(config, url, &description);
+
+        let mut body = String::from("<main class=\"page\">\n<header class=\"header\">\n<div>\n");
+        let _ = writeln!(body, "<h1 class=\"title\">{}</h1>", escape(heading));
+        let _ = writeln!(
+            body,
+            "<p class=\"subtitle\">{}</p>",
+            escape(&config.site.title)
+        );
+        body.push_str("</div>\n");
+        let _ = writeln!(
+            body,
+            "<a class=\"home-button\" href=\"{}\">Home</a>",
+            escape(&absolute_url(&config.site, ""))
+        );
+        body.push_str("</header>\n");
+        let mut last_year = None;
+        for month in posts
+            .chunk_by(|a, b| (a.date.year(), a.date.month()) == (b.date.year(), b.date.month()))
+        {
+            let date = month[0].date;
+            if last_year != Some(date.year()) {
+                last_year = Some(date.year());
+                let _ = writeln!(body, "<h2 class=\"archive-year\">{}</h2>", date.year());
+            }
+            body.push_str("<section class=\"card\">\n<div class=\"archive-month-row\">\n");
+            let _ = writeln!(
+                body,
+                "<div class=\"archive-month-title\">{:02} 月</div>",
+                date.month()
+            );
+            let _ = writeln!(
+                body,
+                "<div class=\"archive-month-count\">{} 篇</div>",
+                month.len()
+            );
+            body.push_str("</div>\n<ul class=\"list\">\n");
+            for post in month {
+                let _ = writeln!(
+                    body,
+                    "<li><a class=\"link-button\" href=\"{}\"><span class=\"archive-date\">{}</span>\
+                     <span class=\"post-entry\"><span>{}</span><span class=\"post-excerpt\">{}</span></span>\
+                     <span class=\"archive-stats\">{}</span></a></li>",
+                    escape(&absolute_url(&config.site, post.url)),
+                    post.date.format("%m-%d"),
+                    escape(post.title),
+                    escape(post.excerpt),
+                    stats_label(post.stats)
+                );
+            }
+            body.push_str("</ul>\n</section>\n");
+        }
+        body.push_str("</main>\n");
+        let data: &[(&str, &str)] = match tag {
+            Some(tag) => &[("view", "topic"), ("tag", tag)],
+            None => &[("view", "archive")],
+        };
+        self.render(&title, &head, &body, data)
+    }
+}
+
+/// Write `html` to `<url>/index.html` under the output dir, returning the file written.
+pub fn write_page(dist_dir: &Path, url: &str, html: &str) -> Result<PathBuf> {
+    let dir = dist_dir.join(url.trim_matches('/'));
+    fs::create_dir_all(&dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    let path = dir.join("index.html");
+    fs::write(&path, html).with_context(|| format!("write to {} failed", path.display()))?;
+    Ok(path)
+}
+
+fn page_title(title: &str, site_title: &str) -> String {
+    if site_title.is_empty() {
+        title.to_owned()
+    } else {
+        format!("{title} · {site_title}")
+    }
+}
+
+/// Description, canonical URL, feed and highlight styles shared by every page.
+fn common_head(config: &SiteConfig, url: &str, description: &str) -> String {
+    let site = &config.site;
+    let canonical = absolute_url(site, url);
+    let mut head = String::new();
+    let _ = writeln!(
+        head,
+        "<meta name=\"description\" content=\"{}\" />",
+        escape(description)
+    );
+    let _ = writeln!(
+        head,
+        "<link rel=\"canonical\" href=\"{}\" />",
+        escape(&canonical)
+    );
+    let _ = writeln!(
+        head,
+        "<meta property=\"og:url\" content=\"{}\" />",
+        escape(&canonical)
+    );
+    let _ = writeln!(
+        head,
+        "<meta property=\"og:description\" content=\"{}\" />",
+        escape(description)
+    );
+    if !site.title.is_empty() {
+        let _ = writeln!(
+            head,
+            "<meta property=\"og:site_name\" content=\"{}\" />",
+            escape(&site.title)
+        );
+    }
+    if config.feed.enabled && has_absolute_base_url(site) {
+        let _ = writeln!(
+            head,
+            "<link rel=\"alternate\" type=\"application/atom+xml\" href=\"{}\" />",
+            escape(&absolute_url(site, ATOM_FILE))
+        );
+    }
+    if config.highlight.enabled && config.highlight.mode == HighlightMode::Css {
+        let _ = writeln!(
+            head,
+            "<link rel=\"stylesheet\" href=\"{}\" />",
+            escape(&absolute_url(site, HIGHLIGHT_CSS_FILE))
+        );
+    }
+    head
+}
+
+/// Same wording as `TextStats::label` in the web app.
+fn stats_label(stats: TextStats) -> String {
+    format!(
+        "{} 字 · {} 分钟",
+        stats.words + stats.cjk_chars,
+        stats.reading_minutes
+    )
+}
@@ -0,0 +1,182 @@
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de};
+use std::{fmt, str::FromStr};
+
+/// Front matter `date`: a day, optionally with a time of day and a UTC offset, e.g.
+/// `2025-01-02`, `2025-01-02 08:30` or `2025-01-02T08:30:00+08:00`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PostDate {
+    pub day: NaiveDate,
+    time: Option<NaiveTime>,
+    offset: Option<FixedOffset>,
+}
+
+const DATE_TIME_FORMATS: [&str; 4] = [
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%d %H:%M",
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y-%m-%dT%H:%M",
+];
+
+impl PostDate {
+    pub fn from_day(day: NaiveDate) -> Self {
+        Self {
+            day,
+            time: None,
+            offset: None,
+        }
+    }
+
+    /// When the post goes live: midnight for a bare day, in `default_offset`
+    /// (or the build machine's zone) when the date has no offset of its own.
+    pub fn publish_at(&self, default_offset: Option<FixedOffset>) -> DateTime<Utc> {
+        let local = self.day.and_time(self.time.unwrap_or(NaiveTime::MIN));
+        let zoned = match self.offset.or(default_offset) {
+            Some(offset) => offset.from_local_datetime(&local).earliest(),
+            None => Local
+                .from_local_datetime(&local)
+                .earliest()
+                .map(|t| t.fixed_offset()),
+        };
+        // a local time skipped by a DST change, treat it as UTC rather than failing
+        zoned.map_or_else(|| local.and_utc(), |t| t.to_utc())
+    }
+}
+
+impl FromStr for PostDate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Ok(t) = DateTime::parse_from_rfc3339(s)
+            .or_else(|_| DateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S %:z"))
+            .or_else(|_| DateTime::parse_from_str(s, "%Y-%m-%d %H:%M %:z"))
+        {
+            return Ok(Self {
+                day: t.date_naive(),
+                time: Some(t.time()),
+                offset: Some(*t.offset()),
+            });
+        }
+        if let Some(t) = DATE_TIME_FORMATS
+            .iter()
+            .find_map(|format| NaiveDateTime::parse_from_str(s, format).ok())
+        {
+            return Ok(Self {
+                day: t.date(),
+                time: Some(t.time()),
+                offset: None,
+            });
+        }
+        NaiveDate::parse_from_str(s, "%Y-%m-%d")
+            .map(Self::from_day)
+            .map_err(|_| {
+                format!("invalid date `{s}`, expected e.g. 2025-01-02, 2025-01-02 08:30 or 2025-01-02T08:30:00+08:00")
+            })
+    }
+}
+
+impl fmt::Display for PostDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.time, self.offset) {
+            (None, _) => write!(f, "{}", self.day.format("%Y-%m-%d")),
+            (Some(time), None) => {
+                write!(f, "{}", self.day.and_time(time).format("%Y-%m-%d %H:%M:%S"))
+            }
+            (Some(time), Some(offset)) => match offset
+                .from_local_datetime(&self.day.and_time(time))
+                .single()
+            {
+                Some(t) => write!(f, "{}", t.to_rfc3339()),
+                None => write!(f, "{}", self.day.and_time(time).format("%Y-%m-%d %H:%M:%S")),
+            },
+        }
+    }
+}
+
+impl Serialize for PostDate {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for PostDate {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().to_utc()
+    }
+
+    fn offset(hours: i32) -> Option<FixedOffset> {
+        FixedOffset::east_opt(hours * 3600)
+    }
+
+    #[test]
+    fn parses_a_bare_day() {
+        let date: PostDate = " 2025-01-02 ".parse().unwrap();
+        assert_eq!(
+            date,
+            PostDate::from_day(NaiveDate::from_ymd_opt(2025, 1, 2).unwrap())
+        );
+        assert_eq!(date.to_string(), "2025-01-02");
+        assert_eq!(date.publish_at(offset(0)), utc("2025-01-02T00:00:00Z"));
+    }
+
+    #[test]
+    fn parses_offsets() {
+        for s in [
+            "2025-01-02T08:30:00+08:00",
+            "2025-01-02 08:30:00 +08:00",
+            "2025-01-02 08:30 +08:00",
+            "2025-01-02T00:30:00Z",
+        ] {
+            let date: PostDate = s.parse().unwrap();
+            assert_eq!(
+                date.publish_at(offset(-5)),
+                utc("2025-01-02T00:30:00Z"),
+                "{s}"
+            );
+        }
+        let date: PostDate = "2025-01-01T23:00:00-05:00".parse().unwrap();
+        assert_eq!(date.day, NaiveDate::from_ymd_opt(2025, 1, 1).unwrap());
+        assert_eq!(date.publish_at(None), utc("2025-01-02T04:00:00Z"));
+        assert_eq!(date.to_string(), "2025-01-01T23:00:00-05:00");
+    }
+
+    #[test]
+    fn times_without_an_offset_use_the_default() {
+        for s in [
+            "2025-01-02 08:30",
+            "2025-01-02T08:30:00",
+            "2025-01-02 08:30:00",
+        ] {
+            let date: PostDate = s.parse().unwrap();
+            assert_eq!(
+                date.publish_at(offset(8)),
+                utc("2025-01-02T00:30:00Z"),
+                "{s}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_other_dates() {
+        for s in [
+            "",
+            "2025/01/02",
+            "2025-13-01",
+            "02-01-2025",
+            "2025-01-02 25:00",
+        ] {
+            assert!(s.parse::<PostDate>().is_err(), "{s}");
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::{collections::BTreeMap, fs, path::Path};
+use tokenize::tokenize;
+
+pub const SEARCH_INDEX_FILE: &str = "search-index.json";
+
+/// Occurrences in the title count this many times as often as in the body.
+const TITLE_BOOST: f32 = 5.0;
+// BM25 parameters
+const K1: f32 = 1.2;
+const B: f32 = 0.75;
+
+/// Inverted index dumped as `search-index.json`. Scores are precomputed BM25 weights,
+/// so a query only has to sum them up per document.
+#[derive(Debug, Default, Serialize)]
+pub struct SearchIndex {
+    docs: Vec<SearchDoc>,
+    /// term -> [(doc id, score)]
+    terms: BTreeMap<String, Vec<(u32, f32)>>,
+}
+
+#[derive(Debug, Serialize)]
+struct SearchDoc {
+    path: String,
+    title: String,
+}
+
+/// What the index needs to know about one post.
+pub struct SearchInput<'a> {
+    pub path: String,
+    pub title: &'a str,
+    /// rendered HTML
+    pub content: &'a str,
+}
+
+impl SearchIndex {
+    pub fn build(inputs: Vec<SearchInput>) -> Self {
+        let mut docs = Vec::with_capacity(inputs.len());
+        // per document: term -> weighted frequency, and the document length
+        let mut freqs: Vec<(BTreeMap<String, f32>, f32)> = Vec::with_capacity(inputs.len());
+        for input in inputs {
+            let mut tf: BTreeMap<String, f32> = BTreeMap::new();
+            let mut len = 0.0;
+            for token in tokenize(input.title) {
+                *tf.entry(token).or_default() += TITLE_BOOST;
+                len += 1.0;
+            }
+            for token in tokenize(&html_to_text(input.content)) {
+                *tf.entry(token).or_default() += 1.0;
+                len += 1.0;
+            }
+            freqs.push((tf, len));
+            docs.push(SearchDoc {
+                path: input.path,
+                title: input.title.to_owned(),
+            });
+        }
+        let n = docs.len() as f32;
+        let avg_len = (freqs.iter().map(|(_, len)| len).sum::<f32>() / n.max(1.0)).max(1.0);
+        let mut doc_freq: BTreeMap<&str, f32> = BTreeMap::new();
+        for (tf, _) in &freqs {
+            for term in tf.keys() {
+                *doc_freq.entry(term).or_default() += 1.0;
+            }
+        }
+        let mut terms: BTreeMap<String, Vec<(u32, f32)>> = BTreeMap::new();
+        for (doc_id, (tf, len)) in freqs.iter().enumerate() {
+            for (term, f) in tf {
+                let df = doc_freq[term.as_str()];
+                let idf = ((n - df + 0.5) / (df + 0.5) + 1.0).ln();
+                let score = idf * f * (K1 + 1.0) / (f + K1 * (1.0 - B + B * len / avg_len));
+                // three decimals is plenty for ranking and keeps the JSON small
+                let score = (score * 1000.0).round() / 1000.0;
+                terms
+                    .entry(term.clone())
+                    .or_default()
+                    .push((doc_id as u32, score));
+            }
+        }
+        Self { docs, terms }
+    }
+
+    pub fn write(&self, dist_dir: &Path) -> Result<()> {
+        let path = dist_dir.join(SEARCH_INDEX_FILE);
+        let json = serde_json::to_string(self).context("serialize search index failed")?;
+        fs::write(&path, json).with_context(|| format!("write to {} failed", path.display()))
+    }
+}
+
+/// Drop tags and decode the handful of entities comrak emits.
+pub fn html_to_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            // tags separate words, `a<br>b` is two words
+            '>' if in_tag => {
+                in_tag = false;
+                text.push(' ');
+            }
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn html_to_text_drops_tags_and_entities() {
+        assert_eq!(
+            html_to_text("<p>a<br>b &amp;lt; &lt;c&gt;</p>"),
+            " a b &lt; <c> "
+        );
+    }
+}
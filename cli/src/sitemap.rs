@@ -0,0 +1,91 @@
+use anyhow::{Context, Result};
+use chrono::NaiveDate;
+use std::{fmt::Write, fs, path::Path};
+
+use crate::config::{RobotsSection, SiteSection};
+use crate::feed::{absolute_url, escape_xml, has_absolute_base_url};
+
+pub const SITEMAP_FILE: &str = "sitemap.xml";
+pub const ROBOTS_FILE: &str = "robots.txt";
+/// Site relative address of the archive page.
+pub const ARCHIVE_URL: &str = "archive/";
+
+pub struct SitemapEntry {
+    /// Site relative, joined to `site.base_url`
+    pub url: String,
+    pub lastmod: NaiveDate,
+}
+
+/// Crawlers only take absolute sitemap URLs, so without an `http(s)://` base URL nothing is written
+/// and an earlier sitemap is removed. Returns whether the sitemap was written.
+pub fn write_sitemap(
+    dist_dir: &Path,
+    site: &SiteSection,
+    entries: &[SitemapEntry],
+) -> Result<bool> {
+    let path = dist_dir.join(SITEMAP_FILE);
+    if !has_absolute_base_url(site) {
+        eprintln!(
+            "warning: `site.base_url` is not an absolute http(s) URL, skipping {SITEMAP_FILE}"
+        );
+        if let Err(e) = fs::remove_file(&path)
+            && e.kind() != std::io::ErrorKind::NotFound
+        {
+            eprintln!("Failed to remove {}: {e}", path.display());
+        }
+        return Ok(false);
+    }
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n");
+    for entry in entries {
+        xml.push_str("  <url>\n");
+        let _ = writeln!(
+            xml,
+            "    <loc>{}</loc>",
+            escape_xml(&absolute_url(site, &percent_encode_path(&entry.url)))
+        );
+        let _ = writeln!(
+            xml,
+            "    <lastmod>{}</lastmod>",
+            entry.lastmod.format("%Y-%m-%d")
+        );
+        xml.push_str("  </url>\n");
+    }
+    xml.push_str("</urlset>\n");
+    fs::write(&path, xml).with_context(|| format!("write to {} failed", path.display()))?;
+    Ok(true)
+}
+
+/// `robots.txt` from the `[robots]` section, pointing at the sitemap when one was written.
+pub fn write_robots(
+    dist_dir: &Path,
+    site: &SiteSection,
+    config: &RobotsSection,
+    with_sitemap: bool,
+) -> Result<()> {
+    let mut robots = format!("User-agent: {}\n", config.user_agent);
+    if config.disallow.is_empty() {
+        robots.push_str("Disallow:\n");
+    }
+    for path in &config.disallow {
+        let _ = writeln!(robots, "Disallow: {path}");
+    }
+    if with_sitemap {
+        let _ = writeln!(robots, "\nSitemap: {}", absolute_url(site, SITEMAP_FILE));
+    }
+    let path = dist_dir.join(ROBOTS_FILE);
+    fs::write(&path, robots).with_context(|| format!("write to {} failed", path.display()))
+}
+
+/// Percent-encode each segment of a site relative path, e.g. `tags/入门/` to `tags/%E5%85%A5%E9%97%A8/`.
+pub fn percent_encode_path(path: &str) -> String {
+    let mut out = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'/' | b'-' | b'.' | b'_' | b'~') {
+            out.push(byte as char);
+        } else {
+            let _ = write!(out, "%{byte:02X}");
+        }
+    }
+    out
+}
@@ -0,0 +1,117 @@
+use comrak::nodes::{AstNode, NodeValue};
+use serde::{Deserialize, Serialize};
+use tokenize::is_cjk;
+
+/// Rough silent reading speeds, English words and CJK characters per minute.
+const WORDS_PER_MINUTE: usize = 230;
+const CJK_CHARS_PER_MINUTE: usize = 500;
+
+/// Length of a post's prose, code blocks, raw HTML and math are not counted.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct TextStats {
+    /// Runs of non-CJK letters and digits
+    pub words: usize,
+    /// CJK characters, each read as one unit
+    pub cjk_chars: usize,
+    pub reading_minutes: usize,
+}
+
+impl TextStats {
+    /// Count the text and inline code under `root`; front matter and code blocks carry no inline children.
+    pub fn from_ast<'a>(root: &'a AstNode<'a>) -> Self {
+        Self::from_text(&plain_text(root))
+    }
+
+    pub fn from_text(text: &str) -> Self {
+        let mut stats = Self::default();
+        let mut in_word = false;
+        for c in text.chars() {
+            if is_cjk(c) {
+                stats.cjk_chars += 1;
+                in_word = false;
+            } else if c.is_alphanumeric() {
+                if !in_word {
+                    stats.words += 1;
+                }
+                in_word = true;
+            } else if !(in_word && matches!(c, '\'' | '’' | '-' | '_')) {
+                // apostrophes and hyphens inside a word don't split it
+                in_word = false;
+            }
+        }
+        let minutes = (stats.words as f64 / WORDS_PER_MINUTE as f64)
+            + (stats.cjk_chars as f64 / CJK_CHARS_PER_MINUTE as f64);
+        stats.reading_minutes = if stats.words + stats.cjk_chars == 0 {
+            0
+        } else {
+            (minutes.ceil() as usize).max(1)
+        };
+        stats
+    }
+}
+
+/// Text and inline code under `node`, with a space wherever a line or block ends.
+pub fn plain_text<'a>(node: &'a AstNode<'a>) -> String {
+    let mut text = String::new();
+    for node in node.descendants() {
+        match &node.data.borrow().value {
+            NodeValue::Text(t) => text.push_str(t),
+            NodeValue::Code(code) => text.push_str(&code.literal),
+            NodeValue::SoftBreak | NodeValue::LineBreak => text.push(' '),
+            // keeps the last word of a block from running into the next one
+            value if value.block() => text.push(' '),
+            _ => {}
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use comrak::{Arena, Options};
+
+    fn stats_of(markdown: &str) -> TextStats {
+        let arena = Arena::new();
+        let mut options = Options::default();
+        options.extension.math_dollars = true;
+        TextStats::from_ast(comrak::parse_document(&arena, markdown, &options))
+    }
+
+    #[test]
+    fn counts_mixed_cjk_and_latin() {
+        let stats = TextStats::from_text("用Rust写一个static site生成器, v2 版本");
+        assert_eq!((stats.words, stats.cjk_chars), (4, 9));
+        assert_eq!(stats.reading_minutes, 1);
+    }
+
+    #[test]
+    fn apostrophes_and_hyphens_stay_inside_words() {
+        let stats =
+            TextStats::from_text("It's a well-known don’t-care snake_case - 'quoted' state-");
+        assert_eq!(stats.words, 7);
+        assert_eq!(stats.cjk_chars, 0);
+    }
+
+    #[test]
+    fn empty_text_takes_no_time() {
+        assert_eq!(TextStats::from_text(" -- ").reading_minutes, 0);
+        let long = "word ".repeat(WORDS_PER_MINUTE + 1);
+        assert_eq!(TextStats::from_text(&long).reading_minutes, 2);
+    }
+
+    #[test]
+    fn skips_code_blocks_and_math() {
+        let stats = stats_of(
+            "# 标题 Title\n\nSome `inline code` 中文\n\n```rust\nfn main() { println!(\"不算\"); }\n```\n\n\
+             Euler $e^{i\\pi} + 1 = 0$ done\n\n$$\n\\sum_{k=1}^n k\n$$\n",
+        );
+        assert_eq!((stats.words, stats.cjk_chars), (6, 4));
+    }
+
+    #[test]
+    fn blocks_do_not_run_into_each_other() {
+        let stats = stats_of("first\n\nsecond\n- third\n- fourth\n");
+        assert_eq!(stats.words, 4);
+    }
+}
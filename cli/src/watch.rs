@@ -0,0 +1,77 @@
+use anyhow::{Context, Result};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc::{self, RecvTimeoutError},
+    time::Duration,
+};
+
+/// How long the tree has to stay quiet before a batch of changes is handed out.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Watch `roots` recursively and call `on_change` with the changed posts, referenced files and
+/// directories after each debounced batch of changes, ignoring hidden files and whatever happens
+/// under `output`. Only returns when the watcher itself goes away.
+pub fn watch_content(
+    roots: &[PathBuf],
+    output: &Path,
+    mut on_change: impl FnMut(&[PathBuf]),
+) -> Result<()> {
+    // 输出目录可能就在内容目录下面, 不排除的话每次构建都会触发下一次构建
+    let output = std::path::absolute(output)
+        .with_context(|| format!("failed to resolve {}", output.display()))?;
+    let absolute_roots: Vec<PathBuf> = roots
+        .iter()
+        .map(std::path::absolute)
+        .collect::<std::io::Result<_>>()
+        .context("failed to resolve the content roots")?;
+    let (tx, rx) = mpsc::channel::<notify::Result<Event>>();
+    let mut watcher = RecommendedWatcher::new(tx, notify::Config::default())
+        .context("failed to create file watcher")?;
+    for root in roots {
+        watcher
+            .watch(root, RecursiveMode::Recursive)
+            .with_context(|| format!("failed to watch {}", root.display()))?;
+    }
+    let mut changed: Vec<PathBuf> = Vec::new();
+    loop {
+        // block until something happens, then keep collecting until it settles down
+        let received = if changed.is_empty() {
+            rx.recv().map_err(|_| RecvTimeoutError::Disconnected)
+        } else {
+            rx.recv_timeout(DEBOUNCE)
+        };
+        match received {
+            Ok(Ok(event)) => collect_changed(event, &absolute_roots, &output, &mut changed),
+            Ok(Err(e)) => eprintln!("Watch error: {e}"),
+            Err(RecvTimeoutError::Timeout) => {
+                changed.sort();
+                changed.dedup();
+                on_change(&changed);
+                changed.clear();
+            }
+            Err(RecvTimeoutError::Disconnected) => return Ok(()),
+        }
+    }
+}
+
+fn collect_changed(event: Event, roots: &[PathBuf], output: &Path, changed: &mut Vec<PathBuf>) {
+    // rebuilding reads every source, reacting to reads would loop forever
+    if matches!(event.kind, EventKind::Access(_)) {
+        return;
+    }
+    // posts link images and other files, so any change counts; editor swap files and `.git/` do not
+    changed.extend(event.paths.into_iter().filter(|p| {
+        let Ok(p) = std::path::absolute(p) else {
+            return false;
+        };
+        let hidden = roots
+            .iter()
+            .find_map(|root| p.strip_prefix(root).ok())
+            .is_some_and(|rel| {
+                rel.components()
+                    .any(|c| c.as_os_str().to_string_lossy().starts_with('.'))
+            });
+        !hidden && !p.starts_with(output)
+    }));
+}
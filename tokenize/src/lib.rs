@@ -0,0 +1,75 @@
+//! The search tokenizer, shared so the index the cli writes and the queries the web app
+//! runs are split the same way.
+
+/// Hiragana, Katakana, Hangul and CJK ideographs: counted per character, searched as bigrams.
+pub fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3040}'..='\u{30ff}'   // Hiragana, Katakana
+        | '\u{3400}'..='\u{4dbf}' // CJK Extension A
+        | '\u{4e00}'..='\u{9fff}' // CJK Unified Ideographs
+        | '\u{ac00}'..='\u{d7af}' // Hangul
+        | '\u{f900}'..='\u{faff}' // CJK Compatibility Ideographs
+    )
+}
+
+/// Lowercased alphanumeric words, plus overlapping bigrams for runs of CJK characters
+/// (a lone CJK character stays a unigram).
+pub fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut word = String::new();
+    let mut cjk_run: Vec<char> = Vec::new();
+    for c in text.chars().chain(std::iter::once(' ')) {
+        if is_cjk(c) {
+            flush_word(&mut word, &mut tokens);
+            cjk_run.push(c);
+        } else if c.is_alphanumeric() {
+            flush_cjk(&mut cjk_run, &mut tokens);
+            word.extend(c.to_lowercase());
+        } else {
+            flush_word(&mut word, &mut tokens);
+            flush_cjk(&mut cjk_run, &mut tokens);
+        }
+    }
+    tokens
+}
+
+fn flush_word(word: &mut String, tokens: &mut Vec<String>) {
+    if !word.is_empty() {
+        tokens.push(std::mem::take(word));
+    }
+}
+
+fn flush_cjk(run: &mut Vec<char>, tokens: &mut Vec<String>) {
+    match run.len() {
+        0 => {}
+        1 => tokens.push(run[0].to_string()),
+        _ => tokens.extend(run.windows(2).map(|w| w.iter().collect())),
+    }
+    run.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_lowercased_words() {
+        assert_eq!(
+            tokenize("Hello, World-2024! x_y"),
+            ["hello", "world", "2024", "x", "y"]
+        );
+        assert!(tokenize(" -- ").is_empty());
+    }
+
+    #[test]
+    fn cjk_runs_become_bigrams() {
+        assert_eq!(tokenize("全文搜索"), ["全文", "文搜", "搜索"]);
+        assert_eq!(tokenize("Rust入门教程"), ["rust", "入门", "门教", "教程"]);
+    }
+
+    #[test]
+    fn a_lone_cjk_character_stays_a_unigram() {
+        assert_eq!(tokenize("第 1 章"), ["第", "1", "章"]);
+        assert_eq!(tokenize("用Rust写"), ["用", "rust", "写"]);
+    }
+}
@@ -1 +1,4 @@
- CONTENT_BASE_URL=https://707state.github.io/note/ trunk build --release --public-url  "https://707state.github.io/note/"
+CONFIG=${SITEGEN_CONFIG:-../sitegen.toml}
+BASE_URL=$(sed -n 's/^base_url *= *"\(.*\)"/\1/p' "$CONFIG")
+BASE_URL=${BASE_URL:-https://707state.github.io/note/}
+CONTENT_BASE_URL=$BASE_URL trunk build --release --public-url "$BASE_URL"
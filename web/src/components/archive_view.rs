@@ -29,7 +29,7 @@ pub fn archive_view(
         on_open_post,
     }: &ArchiveViewProps,
 ) -> Html {
-    let groups = group_by_year_month(&toc_items);
+    let groups = group_by_year_month(toc_items);
 
     let mut last_year: Option<i32> = None;
     html! {
@@ -69,7 +69,11 @@ pub fn archive_view(
                                                 <span class="archive-date">
                                                     { it.date.format("%m-%d").to_string() }
                                                 </span>
-                                                <span>{ it.title }</span>
+                                                <span class="post-entry">
+                                                    <span>{ it.title }</span>
+                                                    <span class="post-excerpt">{ it.excerpt.text }</span>
+                                                </span>
+                                                <span class="archive-stats">{ it.stats.label() }</span>
                                             </button>
                                         </li>
                                     }
@@ -1,13 +1,18 @@
 use crate::components::{TocItem, archive_view::ArchiveView, page::Page, topic_card::TopicCard};
-use std::collections::{HashMap, HashSet};
+use std::collections::HashSet;
 use yew::prelude::*;
 
 #[derive(Properties, PartialEq)]
 pub struct HomeViewProps {
+    #[prop_or_default]
+    pub site_title: String,
     pub toc_items: Vec<TocItem>,
+    /// Tag and the `path` of each post carrying it
     pub topics: Vec<(String, Vec<String>)>,
-    pub title_to_path: HashMap<String, String>,
     pub expanded_topics: HashSet<String>,
+    /// Open on the topics rather than the archive, e.g. when the page was reached at a tag URL
+    #[prop_or_default]
+    pub show_topics: bool,
 
     pub on_toggle_topic: Callback<String>,
     pub on_open_post: Callback<String>,
@@ -21,15 +26,22 @@ enum HomeMode {
 #[function_component(HomeView)]
 pub fn home_view(
     HomeViewProps {
+        site_title,
         toc_items,
         topics,
-        title_to_path,
         expanded_topics,
+        show_topics,
         on_toggle_topic,
         on_open_post,
     }: &HomeViewProps,
 ) -> Html {
-    let mode = use_state(|| HomeMode::Archive);
+    let mode = use_state(|| {
+        if *show_topics {
+            HomeMode::Topics
+        } else {
+            HomeMode::Archive
+        }
+    });
     let set_archive = {
         let mode = mode.clone();
         Callback::from(move |_| mode.set(HomeMode::Archive))
@@ -45,7 +57,9 @@ pub fn home_view(
         html! {
             <header class="header">
                 <div>
-                    <h1 class="title">{ "Home" }</h1>
+                    <h1 class="title">
+                        { if site_title.is_empty() { "Home" } else { site_title.as_str() } }
+                    </h1>
                     <p class="subtitle">
                         {
                             if is_archive { "Archive (by date)" }
@@ -87,13 +101,13 @@ pub fn home_view(
                 html! {
                     <>
                         {
-                            for topics.iter().map(|(topic, titles)| {
+                            for topics.iter().map(|(topic, paths)| {
                                 let is_open = expanded_topics.contains(topic);
                                 html! {
                                     <TopicCard
                                         topic={topic.clone()}
-                                        titles={titles.clone()}
-                                        title_to_path={title_to_path.clone()}
+                                        paths={paths.clone()}
+                                        toc_items={toc_items.clone()}
                                         is_open={is_open}
                                         on_toggle={on_toggle_topic.clone()}
                                         on_open_post={on_open_post.clone()}
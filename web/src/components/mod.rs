@@ -3,6 +3,7 @@ pub mod card;
 pub mod error_view;
 pub mod home_view;
 pub mod loading_view;
+pub mod outline_nav;
 pub mod page;
 pub mod post_view;
 pub mod search_view;
@@ -14,7 +15,18 @@ pub struct FrontMatter {
     pub title: String,
     pub author: String,
     pub tags: Vec<String>,
+    /// `2025-01-02`, optionally followed by a time and offset for scheduled posts
     pub date: Option<String>,
+    /// Front matter keys sitegen does not know about, as written in the post
+    #[serde(default)]
+    pub extra: serde_json::Value,
+}
+
+impl FrontMatter {
+    /// Just the day part of `date`.
+    pub fn day(&self) -> Option<&str> {
+        self.date.as_deref().map(|d| d.get(..10).unwrap_or(d))
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, PartialEq)]
@@ -22,12 +34,66 @@ pub struct PostPayload {
     pub path: String,
     pub modified_at_unix: Option<u64>,
     pub metadata: FrontMatter,
+    #[serde(default)]
+    pub outline: Vec<OutlineItem>,
+    #[serde(default)]
+    pub stats: TextStats,
     pub content: String,
 }
 
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct TextStats {
+    pub words: usize,
+    pub cjk_chars: usize,
+    pub reading_minutes: usize,
+}
+
+impl TextStats {
+    /// e.g. `1234 字 · 5 分钟`, a CJK character and an English word both count as one.
+    pub fn label(&self) -> String {
+        format!(
+            "{} 字 · {} 分钟",
+            self.words + self.cjk_chars,
+            self.reading_minutes
+        )
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct OutlineItem {
+    pub level: u8,
+    pub text: String,
+    pub anchor: String,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct SiteInfo {
+    pub title: String,
+    pub author: String,
+    pub base_url: String,
+}
+
 #[derive(Debug, Clone, Deserialize, PartialEq)]
 pub struct TocItem {
     pub title: String,
     pub path: String,
+    /// Site relative address of the post, empty in indexes from older builds
+    #[serde(default)]
+    pub url: String,
     pub date: NaiveDate,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub stats: TextStats,
+    #[serde(default)]
+    pub excerpt: Excerpt,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct Excerpt {
+    pub text: String,
+    pub html: String,
 }
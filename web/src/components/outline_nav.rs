@@ -0,0 +1,85 @@
+use crate::components::OutlineItem;
+use gloo_events::EventListener;
+use yew::prelude::*;
+
+/// A heading counts as current once its top passes this offset from the viewport top.
+const ACTIVE_OFFSET_PX: f64 = 96.0;
+
+#[derive(Properties, PartialEq)]
+pub struct OutlineNavProps {
+    pub outline: Vec<OutlineItem>,
+}
+
+#[function_component(OutlineNav)]
+pub fn outline_nav(props: &OutlineNavProps) -> Html {
+    let active = use_state_eq(|| None::<String>);
+
+    {
+        let active = active.clone();
+        use_effect_with(props.outline.clone(), move |outline| {
+            let outline = outline.clone();
+            let update = move || active.set(current_anchor(&outline));
+            update();
+            let listener = web_sys::window()
+                .map(|window| EventListener::new(&window, "scroll", move |_| update()));
+            move || drop(listener)
+        });
+    }
+
+    if props.outline.is_empty() {
+        return html! {};
+    }
+    // indent relative to the shallowest heading, posts often start at h2
+    let min_level = props
+        .outline
+        .iter()
+        .map(|item| item.level)
+        .min()
+        .unwrap_or(1);
+
+    html! {
+        <nav class="outline-nav">
+            <p class="outline-title">{ "On this page" }</p>
+            <ul class="outline-list">
+                { for props.outline.iter().map(|item| {
+                    let anchor = item.anchor.clone();
+                    let onclick = Callback::from(move |e: MouseEvent| {
+                        e.prevent_default();
+                        if let Some(el) = web_sys::window()
+                            .and_then(|w| w.document())
+                            .and_then(|d| d.get_element_by_id(&anchor))
+                        {
+                            el.scroll_into_view();
+                        }
+                    });
+                    let is_active = active.as_deref() == Some(item.anchor.as_str());
+                    html! {
+                        <li class={classes!(
+                            "outline-item",
+                            format!("outline-depth-{}", item.level - min_level),
+                            is_active.then_some("is-active"),
+                        )}>
+                            <a href={format!("#{}", item.anchor)} {onclick}>{ item.text.clone() }</a>
+                        </li>
+                    }
+                }) }
+            </ul>
+        </nav>
+    }
+}
+
+/// The last heading scrolled past the offset, or the first one while above all of them.
+fn current_anchor(outline: &[OutlineItem]) -> Option<String> {
+    let document = web_sys::window()?.document()?;
+    let mut current = outline.first().map(|item| item.anchor.clone());
+    for item in outline {
+        let Some(el) = document.get_element_by_id(&item.anchor) else {
+            continue;
+        };
+        if el.get_bounding_client_rect().top() > ACTIVE_OFFSET_PX {
+            break;
+        }
+        current = Some(item.anchor.clone());
+    }
+    current
+}
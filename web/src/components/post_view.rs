@@ -1,5 +1,8 @@
 use crate::components::PostPayload;
-use crate::components::{card::Card, page::Page};
+use crate::components::{card::Card, outline_nav::OutlineNav, page::Page};
+use gloo_events::EventListener;
+use web_sys::HtmlImageElement;
+use web_sys::wasm_bindgen::JsCast;
 use yew::prelude::*;
 
 #[derive(Properties, PartialEq)]
@@ -13,6 +16,12 @@ pub fn post_view(props: &PostViewProps) -> Html {
     let injected =
         yew::virtual_dom::VNode::from_html_unchecked(AttrValue::from(props.post.content.clone()));
 
+    // 图片加载完再淡入, 底下是 cli 内嵌的模糊占位图
+    use_effect_with(props.post.content.clone(), |_| {
+        let listeners = fade_in_images();
+        move || drop(listeners)
+    });
+
     let on_home = {
         let cb = props.on_home.clone();
         Callback::from(move |_| cb.emit(()))
@@ -25,9 +34,10 @@ pub fn post_view(props: &PostViewProps) -> Html {
                 <p class="subtitle">
                     {
                         format!(
-                            "{}{}",
+                            "{}{} · {}",
                             props.post.metadata.author,
-                            props.post.metadata.date.as_deref().map_or(String::new(), |d| format!(" · {d}"))
+                            props.post.metadata.day().map_or(String::new(), |d| format!(" · {d}")),
+                            props.post.stats.label()
                         )
                     }
                 </p>
@@ -39,9 +49,43 @@ pub fn post_view(props: &PostViewProps) -> Html {
     html! {
         <Page {header}>
             <hr class="divider" />
-            <Card class={classes!("article")}>
-                { injected }
-            </Card>
+            <div class="post-layout">
+                <Card class={classes!("article", "fade-images")}>
+                    { injected }
+                </Card>
+                <OutlineNav outline={props.post.outline.clone()} />
+            </div>
         </Page>
     }
 }
+
+/// Mark each post image loaded once it is, ones already in the cache right away.
+fn fade_in_images() -> Vec<EventListener> {
+    let Some(images) = web_sys::window().and_then(|w| w.document()).and_then(|d| {
+        d.query_selector_all(".fade-images .sitegen-picture img")
+            .ok()
+    }) else {
+        return Vec::new();
+    };
+    let mut listeners = Vec::new();
+    for i in 0..images.length() {
+        let Some(img) = images
+            .item(i)
+            .and_then(|node| node.dyn_into::<HtmlImageElement>().ok())
+        else {
+            continue;
+        };
+        if img.complete() {
+            let _ = img.class_list().add_1("is-loaded");
+            continue;
+        }
+        // 加载失败也要显示出来, 不然只剩占位图
+        for event in ["load", "error"] {
+            let target = img.clone();
+            listeners.push(EventListener::once(&img, event, move |_| {
+                let _ = target.class_list().add_1("is-loaded");
+            }));
+        }
+    }
+    listeners
+}
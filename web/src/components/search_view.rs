@@ -1,10 +1,13 @@
 use crate::components::{TocItem, card::Card};
+use crate::search::SharedSearchIndex;
 use web_sys::HtmlInputElement;
 use yew::prelude::*;
 
 #[derive(Properties, PartialEq)]
 pub struct SearchViewProps {
     pub toc_items: Vec<TocItem>,
+    #[prop_or_default]
+    pub search_index: Option<SharedSearchIndex>,
     pub keyword: Option<String>,
     pub on_search: Callback<String>,
     pub on_open_post: Callback<String>,
@@ -14,6 +17,7 @@ pub struct SearchViewProps {
 pub fn search_view(
     SearchViewProps {
         toc_items,
+        search_index,
         keyword,
         on_search,
         on_open_post,
@@ -53,12 +57,20 @@ pub fn search_view(
     };
 
     let normalized = current_keyword.trim().to_lowercase();
-    let results: Vec<&TocItem> = if normalized.is_empty() {
+    // (path, title), ranked by the full-text index when it is available
+    let results: Vec<(String, String)> = if normalized.is_empty() {
         Vec::new()
+    } else if let Some(SharedSearchIndex(index)) = search_index {
+        index
+            .query(&normalized)
+            .into_iter()
+            .map(|(doc, _)| (doc.path.clone(), doc.title.clone()))
+            .collect()
     } else {
         toc_items
             .iter()
             .filter(|item| item.title.to_lowercase().contains(&normalized))
+            .map(|item| (item.path.clone(), item.title.clone()))
             .collect()
     };
 
@@ -77,7 +89,7 @@ pub fn search_view(
                             <div class="search-header-row">
                                 <div class="search-header">
                                     <h2 class="search-title">{ "Search" }</h2>
-                                    <p class="search-subtitle">{ "Find posts by title keyword." }</p>
+                                    <p class="search-subtitle">{ "Find posts by title or content." }</p>
                                 </div>
                                 <button class="search-toggle" onclick={on_toggle.clone()}>{ "收起" }</button>
                             </div>
@@ -105,8 +117,12 @@ pub fn search_view(
                                                 </div>
                                                 <ul class="list search-list">
                                                     {
-                                                        for results.into_iter().map(|item| {
-                                                            let path = item.path.clone();
+                                                        for results.into_iter().map(|(path, title)| {
+                                                            let excerpt = toc_items
+                                                                .iter()
+                                                                .find(|item| item.path == path)
+                                                                .map(|item| item.excerpt.text.clone())
+                                                                .unwrap_or_default();
                                                             let on_open = {
                                                                 let cb = on_open_post.clone();
                                                                 Callback::from(move |_| cb.emit(path.clone()))
@@ -114,7 +130,10 @@ pub fn search_view(
                                                             html! {
                                                                 <li>
                                                                     <button onclick={on_open} class="link-button">
-                                                                        <span>{ item.title.clone() }</span>
+                                                                        <span class="post-entry">
+                                                                            <span>{ title }</span>
+                                                                            <span class="post-excerpt">{ excerpt }</span>
+                                                                        </span>
                                                                     </button>
                                                                 </li>
                                                             }
@@ -1,11 +1,11 @@
-use crate::components::card::Card;
-use std::collections::HashMap;
+use crate::components::{TocItem, card::Card};
 use yew::prelude::*;
 #[derive(Properties, PartialEq)]
 pub struct TopicCardProps {
     pub topic: String,
-    pub titles: Vec<String>,
-    pub title_to_path: HashMap<String, String>,
+    /// `path` of each post under the topic
+    pub paths: Vec<String>,
+    pub toc_items: Vec<TocItem>,
     pub is_open: bool,
 
     pub on_toggle: Callback<String>,
@@ -16,8 +16,8 @@ pub struct TopicCardProps {
 pub fn topic_card(
     TopicCardProps {
         topic,
-        titles,
-        title_to_path,
+        paths,
+        toc_items,
         is_open,
         on_toggle,
         on_open_post,
@@ -39,25 +39,27 @@ pub fn topic_card(
             if *is_open{
                 html! {
                     <ul class="list">
-                        { for titles.iter().map(|title| {
-                            let path = title_to_path.get(title).cloned();
-                            if let Some(path) = path {
+                        { for paths
+                            .iter()
+                            .filter_map(|path| toc_items.iter().find(|item| &item.path == path))
+                            .map(|item| {
                                 let on_open = {
-                                    let path = path.clone();
+                                    let path = item.path.clone();
                                     let cb = on_open_post.clone();
                                     Callback::from(move |_| cb.emit(path.clone()))
                                 };
                                 html! {
                                     <li>
                                         <button onclick={on_open} class="link-button">
-                                            { title }
+                                            <span class="post-entry">
+                                                <span>{ &item.title }</span>
+                                                <span class="post-excerpt">{ &item.excerpt.text }</span>
+                                            </span>
                                         </button>
                                     </li>
                                 }
-                            } else {
-                                html! { <li><span>{ title }</span></li> }
-                            }
-                        })}
+                            })
+                        }
                     </ul>
                 }
             }else{
@@ -1,18 +1,28 @@
 use crate::components::{
-    PostPayload, TocItem, error_view::ErrorView, home_view::HomeView, loading_view::LoadingView,
-    post_view::PostView, search_view::SearchView,
+    PostPayload, SiteInfo, TocItem, error_view::ErrorView, home_view::HomeView,
+    loading_view::LoadingView, post_view::PostView, search_view::SearchView,
 };
+use crate::search::{SearchIndex, SharedSearchIndex};
+use gloo_events::EventListener;
 use gloo_net::http::Request;
 use serde::Deserialize;
 use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
 use yew::prelude::*;
 
 pub mod components;
+pub mod search;
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct IndexPayload {
+    #[serde(default)]
+    pub site: SiteInfo,
+    /// Tag to the `path` of every post carrying it
     pub paragraph_under_certain_topic: HashMap<String, Vec<String>>,
     pub table_of_content: Vec<TocItem>,
+    /// Posts have a page at their `url`, otherwise they are addressed as `?post=<path>`
+    #[serde(default)]
+    pub prerendered: bool,
 }
 
 #[function_component(App)]
@@ -21,22 +31,65 @@ fn app() -> Html {
     let post = use_state(|| None::<PostPayload>);
     let error = use_state(|| None::<String>);
     let is_loading = use_state(|| false);
-    let expanded_topics = use_state(HashSet::<String>::new);
+    // read before `remove_prerendered_page` takes the tag page away
+    let location_topic = use_state(topic_at_location);
+    let expanded_topics = {
+        let topic = (*location_topic).clone();
+        use_state(move || topic.into_iter().collect::<HashSet<String>>())
+    };
     let search_keyword = use_state(String::new);
+    let search_index = use_state(|| None::<SharedSearchIndex>);
+
+    let open_post = {
+        let post = post.clone();
+        let error = error.clone();
+        let is_loading = is_loading.clone();
 
+        Callback::from(move |path: String| {
+            let post = post.clone();
+            let error = error.clone();
+            let is_loading = is_loading.clone();
+
+            wasm_bindgen_futures::spawn_local(async move {
+                is_loading.set(true);
+                error.set(None);
+
+                let req_path = format!("/{}", path.trim_start_matches('/'));
+                let res = Request::get(&content_url(&req_path)).send().await;
+                match res {
+                    Ok(resp) => match resp.json::<PostPayload>().await {
+                        Ok(p) => post.set(Some(p)),
+                        Err(e) => error.set(Some(format!("JSON parse error (post): {e}"))),
+                    },
+                    Err(e) => error.set(Some(format!("Fetch error (post): {e}"))),
+                }
+
+                is_loading.set(false);
+            });
+        })
+    };
     {
         let index = index.clone();
         let error = error.clone();
         let is_loading = is_loading.clone();
+        let open_post = open_post.clone();
 
         use_effect_with((), move |_| {
+            load_highlight_css();
             wasm_bindgen_futures::spawn_local(async move {
                 is_loading.set(true);
                 let res = Request::get(&content_url("/index.json")).send().await;
 
                 match res {
                     Ok(resp) => match resp.json::<IndexPayload>().await {
-                        Ok(p) => index.set(Some(p)),
+                        Ok(p) => {
+                            set_document_title(&p.site.title);
+                            let deep_link = post_at_location(&p.table_of_content);
+                            index.set(Some(p));
+                            if let Some(path) = deep_link {
+                                open_post.emit(path);
+                            }
+                        }
                         Err(e) => error.set(Some(format!("JSON parse error: {e}"))),
                     },
                     Err(e) => {
@@ -49,9 +102,67 @@ fn app() -> Html {
             || ()
         });
     }
+    {
+        let search_index = search_index.clone();
+        use_effect_with((), move |_| {
+            // optional: without it SearchView falls back to matching titles
+            wasm_bindgen_futures::spawn_local(async move {
+                let res = Request::get(&content_url("/search-index.json"))
+                    .send()
+                    .await;
+                if let Ok(resp) = res
+                    && let Ok(idx) = resp.json::<SearchIndex>().await
+                {
+                    search_index.set(Some(SharedSearchIndex(Rc::new(idx))));
+                }
+            });
+
+            || ()
+        });
+    }
+    {
+        // back/forward between the home page and posts
+        let post = post.clone();
+        let open_post = open_post.clone();
+        let toc = (*index)
+            .as_ref()
+            .map(|p| p.table_of_content.clone())
+            .unwrap_or_default();
+        use_effect_with(toc, move |toc| {
+            let toc = toc.clone();
+            let listener = web_sys::window().map(|window| {
+                EventListener::new(&window, "popstate", move |_| match post_at_location(&toc) {
+                    Some(path) => open_post.emit(path),
+                    None => post.set(None),
+                })
+            });
+            move || drop(listener)
+        });
+    }
     let on_home = {
         let post = post.clone();
-        Callback::from(move |_| post.set(None))
+        Callback::from(move |_| {
+            push_location("");
+            post.set(None);
+        })
+    };
+    let on_open_post = {
+        let index = index.clone();
+        let open_post = open_post.clone();
+        Callback::from(move |path: String| {
+            let url = (*index).as_ref().and_then(|p| {
+                let item = p.table_of_content.iter().find(|item| item.path == path)?;
+                if !p.prerendered {
+                    // a static host has nothing at `url` to reload
+                    return Some(format!("?post={}", encode_uri_component(&item.path)));
+                }
+                (!item.url.is_empty()).then(|| item.url.clone())
+            });
+            if let Some(url) = url {
+                push_location(&url);
+            }
+            open_post.emit(path);
+        })
     };
 
     let on_toggle_topic = {
@@ -66,34 +177,6 @@ fn app() -> Html {
             expanded_topics.set(next);
         })
     };
-    let on_open_post = {
-        let post = post.clone();
-        let error = error.clone();
-        let is_loading = is_loading.clone();
-
-        Callback::from(move |path: String| {
-            let post = post.clone();
-            let error = error.clone();
-            let is_loading = is_loading.clone();
-
-            wasm_bindgen_futures::spawn_local(async move {
-                is_loading.set(true);
-                error.set(None);
-
-                let req_path = format!("/{}", path.trim_start_matches('/'));
-                let res = Request::get(&content_url(&req_path)).send().await;
-                match res {
-                    Ok(resp) => match resp.json::<PostPayload>().await {
-                        Ok(p) => post.set(Some(p)),
-                        Err(e) => error.set(Some(format!("JSON parse error (post): {e}"))),
-                    },
-                    Err(e) => error.set(Some(format!("Fetch error (post): {e}"))),
-                }
-
-                is_loading.set(false);
-            });
-        })
-    };
     let on_search = {
         let search_keyword = search_keyword.clone();
         Callback::from(move |keyword: String| {
@@ -101,6 +184,22 @@ fn app() -> Html {
         })
     };
 
+    {
+        // the prerendered page stays up until the app can show the same thing
+        let ready = error.is_some()
+            || post.is_some()
+            || (!*is_loading
+                && (*index)
+                    .as_ref()
+                    .is_some_and(|p| post_at_location(&p.table_of_content).is_none()));
+        use_effect_with(ready, |ready| {
+            if *ready {
+                remove_prerendered_page();
+            }
+            || ()
+        });
+    }
+
     if let Some(err) = (*error).clone() {
         return html! {
             <ErrorView message={err} on_home={on_home.clone()} />
@@ -122,12 +221,6 @@ fn app() -> Html {
             <LoadingView text={"No index data yet"} />
         };
     };
-    let mut map: HashMap<String, String> = HashMap::new();
-    for item in &index_payload.table_of_content {
-        map.insert(item.title.clone(), item.path.clone());
-    }
-    let title_to_path: HashMap<String, String> = map.clone();
-
     // sort topics by name
     let mut topics: Vec<(String, Vec<String>)> = index_payload
         .paragraph_under_certain_topic
@@ -145,15 +238,17 @@ fn app() -> Html {
     html! {
         <div class="home-layout">
             <HomeView
+                site_title={index_payload.site.title.clone()}
                 toc_items={toc_items.clone()}
                 topics={topics}
-                title_to_path={title_to_path}
                 expanded_topics={expanded}
+                show_topics={location_topic.is_some()}
                 on_toggle_topic={on_toggle_topic}
                 on_open_post={on_open_post.clone()}
             />
             <SearchView
                 toc_items={toc_items}
+                search_index={(*search_index).clone()}
                 keyword={search_keyword}
                 on_search={on_search}
                 on_open_post={on_open_post}
@@ -163,11 +258,119 @@ fn app() -> Html {
 }
 
 fn main() {
-    yew::Renderer::<App>::new().render();
+    // pages written by `sitegen build` keep `#app` hidden until the app takes over
+    match web_sys::window()
+        .and_then(|w| w.document())
+        .and_then(|d| d.get_element_by_id("app"))
+    {
+        Some(root) => yew::Renderer::<App>::with_root(root).render(),
+        None => yew::Renderer::<App>::new().render(),
+    };
+}
+
+fn remove_prerendered_page() {
+    if let Some(page) = web_sys::window()
+        .and_then(|w| w.document())
+        .and_then(|d| d.get_element_by_id("prerender"))
+    {
+        page.remove();
+    }
+}
+
+/// The tag whose prerendered page is showing, so the app opens on that topic instead of the archive.
+fn topic_at_location() -> Option<String> {
+    let page = web_sys::window()?
+        .document()?
+        .get_element_by_id("prerender")?;
+    (page.get_attribute("data-view")? == "topic")
+        .then(|| page.get_attribute("data-tag"))
+        .flatten()
+}
+
+/// Pull in the `highlight.css` the cli writes next to the content JSON.
+fn load_highlight_css() {
+    let Some(document) = web_sys::window().and_then(|w| w.document()) else {
+        return;
+    };
+    let Some(head) = document.head() else {
+        return;
+    };
+    if let Ok(link) = document.create_element("link") {
+        let _ = link.set_attribute("rel", "stylesheet");
+        let _ = link.set_attribute("href", &content_url("/highlight.css"));
+        let _ = head.append_child(&link);
+    }
+}
+
+/// The post whose `url` the address bar points at, so links from feeds open the post directly.
+/// Unlisted posts are not in the toc, their prerendered page names the post instead.
+/// Without prerendered pages the post's JSON path comes as `?post=<path>`.
+fn post_at_location(toc: &[TocItem]) -> Option<String> {
+    let window = web_sys::window()?;
+    let search = window.location().search().unwrap_or_default();
+    let query = search.trim_start_matches('?').split('&');
+    if let Some(path) = query.filter_map(|pair| pair.strip_prefix("post=")).next() {
+        return web_sys::js_sys::decode_uri_component(path)
+            .ok()
+            .map(String::from)
+            .filter(|path| !path.is_empty());
+    }
+    let pathname = window.location().pathname().ok()?;
+    let pathname = web_sys::js_sys::decode_uri(&pathname)
+        .map(String::from)
+        .unwrap_or(pathname);
+    let pathname = pathname.trim_end_matches('/');
+    toc.iter()
+        .find(|item| !item.url.is_empty() && pathname.ends_with(&format!("/{}", item.url)))
+        .map(|item| item.path.clone())
+        .or_else(|| {
+            window
+                .document()?
+                .get_element_by_id("prerender")?
+                .get_attribute("data-post")
+        })
+}
+
+/// Show `url` (site relative, empty for the home page) in the address bar without reloading.
+fn push_location(url: &str) {
+    if let Some(history) = web_sys::window().and_then(|w| w.history().ok()) {
+        let _ = history.push_state_with_url(
+            &web_sys::wasm_bindgen::JsValue::NULL,
+            "",
+            Some(&content_url(url)),
+        );
+    }
+}
+
+fn encode_uri_component(text: &str) -> String {
+    web_sys::js_sys::encode_uri_component(text).into()
+}
+
+fn set_document_title(title: &str) {
+    if title.is_empty() {
+        return;
+    }
+    if let Some(document) = web_sys::window().and_then(|w| w.document()) {
+        document.set_title(title);
+    }
+}
+
+/// `<meta name="content-base-url">` in the page wins over the compile time `CONTENT_BASE_URL`,
+/// so `sitegen serve` can point a release build at local content.
+fn content_base_url() -> String {
+    web_sys::window()
+        .and_then(|w| w.document())
+        .and_then(|d| {
+            d.query_selector("meta[name=content-base-url]")
+                .ok()
+                .flatten()
+        })
+        .and_then(|meta| meta.get_attribute("content"))
+        .unwrap_or_else(|| option_env!("CONTENT_BASE_URL").unwrap_or("").to_string())
 }
 
 fn content_url(path: &str) -> String {
-    let base = option_env!("CONTENT_BASE_URL").unwrap_or("");
+    let base = content_base_url();
     let base = base.trim_end_matches('/');
     let path = path.trim_start_matches('/');
     if base.is_empty() {
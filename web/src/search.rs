@@ -0,0 +1,63 @@
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    rc::Rc,
+};
+use tokenize::tokenize;
+
+/// `search-index.json` written by the cli, see `cli/src/search.rs`.
+#[derive(Debug, Deserialize)]
+pub struct SearchIndex {
+    pub docs: Vec<SearchDoc>,
+    /// term -> [(doc id, score)]
+    pub terms: BTreeMap<String, Vec<(u32, f32)>>,
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct SearchDoc {
+    pub path: String,
+    pub title: String,
+}
+
+/// Shared handle so props compare by pointer instead of walking the whole index.
+#[derive(Debug, Clone)]
+pub struct SharedSearchIndex(pub Rc<SearchIndex>);
+
+impl PartialEq for SharedSearchIndex {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// A term that only prefixes the query token counts this much.
+const PREFIX_WEIGHT: f32 = 0.5;
+
+impl SearchIndex {
+    /// Documents matching `query`, best first. The last token also matches as a prefix,
+    /// so results show up while typing and a single CJK character finds its bigrams.
+    pub fn query(&self, query: &str) -> Vec<(&SearchDoc, f32)> {
+        let tokens = tokenize(query);
+        let mut scores: HashMap<u32, f32> = HashMap::new();
+        for (i, token) in tokens.iter().enumerate() {
+            let is_last = i + 1 == tokens.len();
+            for (term, postings) in self.terms.range(token.clone()..) {
+                let weight = if term == token {
+                    1.0
+                } else if is_last && term.starts_with(token.as_str()) {
+                    PREFIX_WEIGHT
+                } else {
+                    break;
+                };
+                for (doc, score) in postings {
+                    *scores.entry(*doc).or_default() += score * weight;
+                }
+            }
+        }
+        let mut ranked: Vec<(&SearchDoc, f32)> = scores
+            .into_iter()
+            .filter_map(|(doc, score)| self.docs.get(doc as usize).map(|d| (d, score)))
+            .collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.path.cmp(&b.0.path)));
+        ranked
+    }
+}